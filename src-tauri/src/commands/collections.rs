@@ -0,0 +1,76 @@
+use std::path::Path;
+use tauri::State;
+use crate::error::Result;
+use crate::services::index_archive::{self, ArchiveManifest, ConflictPolicy, ImportSummary};
+use crate::services::vector_store::{Collection, SharedVectorStore};
+
+/// Create a new collection (workspace) for indexed documents
+#[tauri::command]
+pub async fn create_collection(store: State<'_, SharedVectorStore>, name: String) -> Result<Collection> {
+    store.get()?.create_collection(&name)
+}
+
+/// Rename a collection
+#[tauri::command]
+pub async fn rename_collection(
+    store: State<'_, SharedVectorStore>,
+    collection_id: String,
+    name: String,
+) -> Result<Collection> {
+    store.get()?.rename_collection(&collection_id, &name)
+}
+
+/// List all collections with their chunk counts
+#[tauri::command]
+pub async fn list_collections(store: State<'_, SharedVectorStore>) -> Result<Vec<Collection>> {
+    store.get()?.list_collections()
+}
+
+/// Delete a collection together with everything indexed into it
+#[tauri::command]
+pub async fn delete_collection(store: State<'_, SharedVectorStore>, collection_id: String) -> Result<()> {
+    store.get()?.delete_collection(&collection_id)
+}
+
+/// Export a collection to a portable index archive at `path`
+#[tauri::command]
+pub async fn export_collection(
+    store: State<'_, SharedVectorStore>,
+    collection_id: String,
+    path: String,
+) -> Result<ArchiveManifest> {
+    let vector_store = store.get()?;
+    index_archive::export_collection(&vector_store, &collection_id, Path::new(&path))
+}
+
+/// Read an index archive's manifest without importing it
+#[tauri::command]
+pub async fn inspect_index_archive(path: String) -> Result<ArchiveManifest> {
+    index_archive::read_manifest(Path::new(&path))
+}
+
+/// Import an index archive. Without a target collection the archive goes
+/// into the collection with the archived name, which is created if needed.
+/// `provider` selects the embedding model the archive must match.
+#[tauri::command]
+pub async fn import_collection(
+    store: State<'_, SharedVectorStore>,
+    path: String,
+    provider: String,
+    collection: Option<String>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<ImportSummary> {
+    use crate::services::embedding::EmbeddingService;
+    
+    let path = Path::new(&path);
+    let vector_store = store.get()?;
+    let embedding_model = EmbeddingService::new(&provider, "", None).model().to_string();
+    
+    index_archive::import_collection(
+        &vector_store,
+        path,
+        collection.as_deref(),
+        &embedding_model,
+        on_conflict.unwrap_or_default(),
+    )
+}
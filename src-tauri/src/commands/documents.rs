@@ -1,7 +1,15 @@
 use serde::Serialize;
+use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
+use tauri::State;
 use crate::error::{AppError, Result};
+use crate::services::document_pipeline::{chunk_id, SourceMap, TextChunk};
+use crate::services::epub;
+use crate::services::html::html_to_markdown;
+use crate::services::office_documents;
+use crate::services::query_expansion::QueryExpansionCache;
+use crate::services::vector_store::{ChunkLocation, DocumentChunk, DocumentRecord, Metadata, SearchResult, SharedVectorStore};
 
 /// Maximum file size we'll attempt to read (50 MB). Beyond this we refuse
 /// rather than risk OOM or extreme parse latency for poorly-formed input.
@@ -10,7 +18,7 @@ const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
 /// reads of unexpected binary types (e.g. exe, dll) and makes the surface
 /// callable from the frontend explicit.
 const ALLOWED_EXTENSIONS: &[&str] = &[
-    "pdf", "txt", "md", "rst", "html", "htm",
+    "pdf", "txt", "md", "rst", "html", "htm", "docx", "pptx", "xlsx", "odt", "epub",
     "py", "js", "ts", "tsx", "jsx", "rs", "java", "cpp", "c", "h", "hpp",
     "go", "rb", "php", "swift", "kt", "cs", "json", "yaml", "yml", "toml",
     "xml", "csv", "sh", "ps1", "sql", "log", "conf", "ini", "env",
@@ -20,8 +28,86 @@ fn is_allowed_extension(ext: &str) -> bool {
     ALLOWED_EXTENSIONS.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext))
 }
 
+/// Text extracted from a document
+struct ExtractedDocument {
+    text: String,
+    /// Byte offset in `text` at which each page starts, for paginated
+    /// formats; empty otherwise
+    page_starts: Vec<usize>,
+    /// Byte offset in `text` at which each chapter starts, with its title,
+    /// for books; empty otherwise
+    chapters: Vec<(usize, Option<String>)>,
+}
+
+impl ExtractedDocument {
+    fn text(text: String) -> Self {
+        Self { text, page_starts: Vec::new(), chapters: Vec::new() }
+    }
+    
+    /// Pages (or slides) separated by a blank line
+    fn pages(pages: Vec<String>) -> Self {
+        let mut document = Self { page_starts: Vec::with_capacity(pages.len()), ..Self::text(String::new()) };
+        for page in pages {
+            if !document.text.is_empty() {
+                document.text.push_str("\n\n");
+            }
+            document.page_starts.push(document.text.len());
+            document.text.push_str(&page);
+        }
+        document
+    }
+    
+    /// Chapters separated by a blank line, each under its title as a
+    /// top-level heading. Chapters without text (such as covers) are left out.
+    fn chapters(chapters: Vec<(Option<String>, String)>) -> Self {
+        let mut document = Self::text(String::new());
+        for (title, text) in chapters {
+            if text.trim().is_empty() {
+                continue;
+            }
+            if !document.text.is_empty() {
+                document.text.push_str("\n\n");
+            }
+            document.chapters.push((document.text.len(), title.clone()));
+            // Most chapters already open with their title
+            let first_line = text.lines().next().unwrap_or_default();
+            match title {
+                Some(title) if first_line.trim_start_matches('#').trim() != title => {
+                    document.text.push_str(&format!("# {}\n\n", title));
+                }
+                _ => {}
+            }
+            document.text.push_str(&text);
+        }
+        document
+    }
+    
+    /// Number (from 1) and title of the chapter containing byte `offset`
+    fn chapter_at(&self, offset: usize) -> Option<(usize, Option<&str>)> {
+        let index = self.chapters.partition_point(|(start, _)| *start <= offset).checked_sub(1)?;
+        Some((index + 1, self.chapters[index].1.as_deref()))
+    }
+}
+
+/// Read a document's text for the chat context. Long documents are
+/// truncated here, to stay within the model's token limits; indexing reads
+/// the full text through `extract_document`.
 #[tauri::command]
 pub async fn read_document_content(file_path: String) -> Result<String> {
+    let text = extract_document(&file_path)?.text;
+    let max_chars = 100_000;
+    // Find a valid UTF-8 boundary after max_chars characters
+    match text.char_indices().nth(max_chars) {
+        Some((byte_idx, _)) => Ok(format!(
+            "{}...\n\n[Content truncated - showing first {} characters]",
+            &text[..byte_idx],
+            max_chars
+        )),
+        None => Ok(text),
+    }
+}
+
+fn extract_document(file_path: &str) -> Result<ExtractedDocument> {
     // Reject obvious traversal patterns. We cannot meaningfully sandbox the
     // filesystem here (the user's own dialog picks arbitrary absolute paths)
     // but we can still refuse paths whose components include `..`, which are
@@ -31,7 +117,7 @@ pub async fn read_document_content(file_path: String) -> Result<String> {
         return Err(AppError::File("Path traversal patterns are not allowed".to_string()));
     }
 
-    let path = Path::new(&file_path);
+    let path = Path::new(file_path);
 
     if !path.exists() {
         return Err(AppError::File(format!("File not found: {}", file_path)));
@@ -58,281 +144,1004 @@ pub async fn read_document_content(file_path: String) -> Result<String> {
         return Err(AppError::File(format!("Unsupported file type: .{}", extension)));
     }
 
-    let content = match extension.as_str() {
+    let document = match extension.as_str() {
         "pdf" => extract_pdf_text(path)?,
+        // Web pages are converted to Markdown without their navigation,
+        // header and footer
         "html" | "htm" => {
-            let html = fs::read_to_string(path).map_err(|e| AppError::File(e.to_string()))?;
-            strip_html_tags(&html)
+            let bytes = fs::read(path).map_err(|e| AppError::File(e.to_string()))?;
+            ExtractedDocument::text(html_to_markdown(&String::from_utf8_lossy(&bytes)))
         }
+        // Office formats are converted to Markdown so that chunking follows
+        // their headings and tables; each slide counts as a page
+        "docx" => ExtractedDocument::text(office_documents::docx_to_markdown(path)?),
+        "odt" => ExtractedDocument::text(office_documents::odt_to_markdown(path)?),
+        "pptx" => ExtractedDocument::pages(office_documents::pptx_slides(path)?),
+        "xlsx" => ExtractedDocument::text(office_documents::xlsx_to_markdown(path)?),
+        // Chapter titles become headings, so chunks carry them as breadcrumbs
+        "epub" => ExtractedDocument::chapters(
+            epub::chapters(path)?
+                .into_iter()
+                .map(|chapter| (chapter.title, html_to_markdown(&chapter.xhtml)))
+                .collect(),
+        ),
         _ => {
             // Text-based formats. Use lossy UTF-8 for files that contain mixed
             // encodings (e.g. log files) so we still surface readable content.
             let bytes = fs::read(path).map_err(|e| AppError::File(e.to_string()))?;
-            String::from_utf8(bytes.clone()).unwrap_or_else(|_| String::from_utf8_lossy(&bytes).into_owned())
+            ExtractedDocument::text(String::from_utf8(bytes.clone()).unwrap_or_else(|_| String::from_utf8_lossy(&bytes).into_owned()))
         }
     };
 
-    // Truncate if too long (to avoid token limits)
-    let max_chars = 100_000;
-    if content.len() > max_chars {
-        // Find a valid UTF-8 boundary at or before max_chars
-        let truncated = match content.char_indices().nth(max_chars) {
-            Some((byte_idx, _)) => &content[..byte_idx],
-            None => &content, // fewer than max_chars characters
-        };
-        Ok(format!("{}...\n\n[Content truncated - showing first {} characters]", truncated, max_chars))
-    } else {
-        Ok(content)
-    }
+    Ok(document)
 }
 
-fn extract_pdf_text(path: &Path) -> Result<String> {
-    use pdf_extract::extract_text;
+fn extract_pdf_text(path: &Path) -> Result<ExtractedDocument> {
+    use pdf_extract::extract_text_by_pages;
     
-    extract_text(path)
+    extract_text_by_pages(path)
+        .map(ExtractedDocument::pages)
         .map_err(|e| AppError::File(format!("Failed to extract PDF text: {}", e)))
 }
 
-fn strip_html_tags(html: &str) -> String {
-    let mut result = String::new();
-    let mut in_tag = false;
-    let mut in_script = false;
-    let mut in_style = false;
-    
-    let html_lower = html.to_lowercase();
-    let chars: Vec<char> = html.chars().collect();
-    let chars_lower: Vec<char> = html_lower.chars().collect();
-    
-    let mut i = 0;
-    while i < chars.len() {
-        if chars[i] == '<' {
-            in_tag = true;
-            // Check for script/style tags
-            let remaining: String = chars_lower[i..].iter().collect();
-            if remaining.starts_with("<script") {
-                in_script = true;
-            } else if remaining.starts_with("</script") {
-                in_script = false;
-            } else if remaining.starts_with("<style") {
-                in_style = true;
-            } else if remaining.starts_with("</style") {
-                in_style = false;
-            }
-        } else if chars[i] == '>' {
-            in_tag = false;
-        } else if !in_tag && !in_script && !in_style {
-            result.push(chars[i]);
-        }
-        i += 1;
-    }
-    
-    // Clean up whitespace
-    let lines: Vec<&str> = result.lines()
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty())
-        .collect();
-    
-    lines.join("\n")
-}
-
 // ============= RAG Commands =============
 
 #[derive(Debug, Serialize)]
 pub struct IndexResult {
     pub document_id: String,
     pub chunks_created: usize,
+    /// Chunks whose embedding was carried over from the previous index
+    pub chunks_reused: usize,
     pub success: bool,
     pub error: Option<String>,
 }
 
+impl IndexResult {
+    fn failed(document_id: String, error: String) -> Self {
+        Self {
+            document_id,
+            chunks_created: 0,
+            chunks_reused: 0,
+            success: false,
+            error: Some(error),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct SemanticSearchResult {
+    pub document_id: String,
     pub document_name: String,
     pub content: String,
     pub score: f32,
+    pub keyword_score: Option<f32>,
+    pub rerank_score: Option<f32>,
+    pub chunk_index: i32,
+    pub metadata: Metadata,
+    /// Character offsets, lines and pages the chunk came from
+    pub location: Option<ChunkLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_reason: Option<FilterReason>,
+}
+
+/// Why a retrieved chunk was left out of the results
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterReason {
+    /// Similarity was below `similarity_threshold`
+    BelowThreshold,
+    /// More than `max_context_chunks` candidates passed the threshold
+    ChunkLimit,
+    /// Nearly identical to a higher-ranked result
+    Duplicate,
+}
+
+/// A stored chunk as shown when inspecting a document (without its embedding)
+#[derive(Debug, Serialize)]
+pub struct ChunkInfo {
+    pub id: String,
     pub chunk_index: i32,
+    pub token_count: i32,
+    pub content: String,
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SemanticSearchResponse {
+    pub results: Vec<SemanticSearchResult>,
+    pub dropped: Vec<SemanticSearchResult>,
+    pub similarity_threshold: f32,
+    pub max_chunks: usize,
 }
 
-/// Index a document for semantic search
+/// Collect file-level metadata (path, type, modified date) for a document
+/// and merge in caller-supplied fields such as tags or author.
+fn document_metadata(file_path: &str, extra: Option<Metadata>) -> Metadata {
+    let path = Path::new(file_path);
+    let mut metadata = Metadata::new();
+    
+    metadata.insert("file_path".to_string(), file_path.into());
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        metadata.insert("file_type".to_string(), ext.to_lowercase().into());
+    }
+    if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+        let modified: chrono::DateTime<chrono::Utc> = modified.into();
+        metadata.insert("modified".to_string(), modified.to_rfc3339().into());
+    }
+    
+    if let Some(extra) = extra {
+        metadata.extend(extra);
+    }
+    
+    metadata
+}
+
+/// Hex-encoded SHA-256 of a document's extracted text
+fn content_hash(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+    
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Index a document for semantic search into a collection
+/// (the default collection if none is given).
+///
+/// `chunking_strategy` overrides the strategy configured for the file's
+/// extension (`chunking_strategies`, falling back to `chunking_strategy`).
+/// The strategy used is recorded in the document's registry entry.
 #[tauri::command]
 pub async fn index_document(
+    store: State<'_, SharedVectorStore>,
     document_id: String,
     document_name: String,
     file_path: String,
     provider: String,
     api_key: String,
+    collection: Option<String>,
+    metadata: Option<Metadata>,
+    chunking_strategy: Option<String>,
 ) -> Result<IndexResult> {
-    use crate::services::document_pipeline::DocumentPipeline;
+    use crate::config::load_config;
+    use crate::services::document_pipeline::{ChunkingStrategy, DocumentFormat, DocumentPipeline};
     use crate::services::embedding::EmbeddingService;
-    use crate::services::vector_store::{VectorStore, DocumentChunk};
+    use crate::services::semantic_chunker::chunk_semantically;
+    use crate::services::vector_store::{IndexStatus, DEFAULT_COLLECTION};
     
-    // Read document content
-    let content = match read_document_content(file_path.clone()).await {
-        Ok(c) => c,
-        Err(e) => return Ok(IndexResult {
-            document_id,
-            chunks_created: 0,
-            success: false,
-            error: Some(e.to_string()),
-        }),
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    let metadata = document_metadata(&file_path, metadata);
+    let extension = metadata.get("file_type").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+    
+    let config = load_config();
+    let strategy_name = chunking_strategy
+        .or_else(|| config.chunking_strategies.get(&extension).cloned())
+        .unwrap_or_else(|| config.chunking_strategy.clone());
+    let Some(strategy) = ChunkingStrategy::parse(&strategy_name) else {
+        return Ok(IndexResult::failed(document_id, format!("Unknown chunking strategy: {}", strategy_name)));
     };
     
     // Initialize services
     let pipeline = DocumentPipeline::new();
     let embedding_service = EmbeddingService::new(&provider, &api_key, None);
-    let vector_store = match VectorStore::new() {
+    let vector_store = match store.get() {
         Ok(vs) => vs,
-        Err(e) => return Ok(IndexResult {
-            document_id,
-            chunks_created: 0,
-            success: false,
-            error: Some(format!("Failed to open vector store: {}", e)),
-        }),
+        Err(e) => return Ok(IndexResult::failed(document_id, format!("Failed to open vector store: {}", e))),
+    };
+    
+    if let Err(e) = vector_store.ensure_collection(&collection_id) {
+        return Ok(IndexResult::failed(document_id, e.to_string()));
+    }
+    
+    let mut record = DocumentRecord {
+        id: document_id.clone(),
+        collection_id: collection_id.clone(),
+        name: document_name.clone(),
+        path: file_path.clone(),
+        size: fs::metadata(&file_path).map(|m| m.len() as i64).unwrap_or(0),
+        content_hash: String::new(),
+        mtime: metadata.get("modified").and_then(|m| m.as_str()).map(String::from),
+        chunk_count: 0,
+        embedding_model: embedding_service.model().to_string(),
+        indexed_at: None,
+        status: IndexStatus::Indexing,
+        last_error: None,
+        chunking_strategy: strategy.as_str().to_string(),
+    };
+    
+    // Read document content
+    let document = match extract_document(&file_path) {
+        Ok(d) => d,
+        Err(e) => {
+            record.status = IndexStatus::Failed;
+            record.last_error = Some(e.to_string());
+            let _ = vector_store.set_document_status(&record);
+            return Ok(IndexResult::failed(document_id, e.to_string()));
+        }
     };
+    let content = &document.text;
+    record.content_hash = content_hash(content);
     
-    // Remove existing chunks for this document
-    let _ = vector_store.remove_document(&document_id);
+    // Mark as in progress. Any previously indexed chunks stay searchable
+    // until the new set is complete.
+    let _ = vector_store.set_document_status(&record);
     
-    // Chunk the document (512 tokens per chunk, 50 token overlap)
-    let text_chunks = pipeline.chunk_text(&content, 512, 50);
+    // Chunk the document
+    let format = DocumentFormat::from_extension(&extension);
+    let text_chunks = if strategy == ChunkingStrategy::Semantic {
+        let min_tokens = config.semantic_chunk_min_size as usize;
+        match chunk_semantically(&pipeline, &embedding_service, content, min_tokens, config.chunk_size as usize).await {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                let error = format!("Failed to embed sentences for semantic chunking: {}", e);
+                record.status = IndexStatus::Failed;
+                record.last_error = Some(error.clone());
+                let _ = vector_store.set_document_status(&record);
+                return Ok(IndexResult::failed(document_id, error));
+            }
+        }
+    } else {
+        pipeline.chunk(content, strategy, format, config.chunk_size as usize, config.chunk_overlap as usize)
+    };
+    if text_chunks.is_empty() {
+        record.status = IndexStatus::Failed;
+        record.last_error = Some("Document produced no chunks".to_string());
+        let _ = vector_store.set_document_status(&record);
+        return Ok(IndexResult::failed(document_id, "Document produced no chunks".to_string()));
+    }
+    
+    // Chunks whose text is unchanged since the last index with the same
+    // model keep their embeddings; only new or edited text is embedded
+    let previous_embeddings: HashMap<String, Vec<f32>> = match vector_store.get_document(&collection_id, &document_id) {
+        Ok(Some(previous)) if previous.embedding_model == record.embedding_model => vector_store
+            .get_document_chunks(&collection_id, &document_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| (c.content, c.embedding))
+            .collect(),
+        _ => HashMap::new(),
+    };
     
-    let mut chunks_created = 0;
+    let source_map = SourceMap::new(content, &document.page_starts);
+    let mut chunks = Vec::with_capacity(text_chunks.len());
+    let mut chunks_reused = 0;
     
     for (index, text_chunk) in text_chunks.iter().enumerate() {
-        // Generate embedding
+        let location = source_map.locate(&text_chunk.span);
+        let chapter = document.chapter_at(text_chunk.span.start);
+        if let Some(embedding) = previous_embeddings.get(&text_chunk.text) {
+            chunks_reused += 1;
+            chunks.push(document_chunk(&record, &metadata, index, text_chunk, location, chapter, embedding.clone()));
+            continue;
+        }
+        
+        // A single failed embedding fails the whole document rather than
+        // leaving it partially indexed
         let embedding = match embedding_service.embed(&text_chunk.text).await {
             Ok(e) => e,
-            Err(_) => continue, // Skip failed embeddings
-        };
-        
-        let chunk = DocumentChunk {
-            id: text_chunk.id.clone(),
-            document_id: document_id.clone(),
-            document_name: document_name.clone(),
-            content: text_chunk.text.clone(),
-            embedding,
-            chunk_index: index as i32,
-            token_count: text_chunk.token_count as i32,
+            Err(e) => {
+                let error = format!("Failed to embed chunk {} of {}: {}", index + 1, text_chunks.len(), e);
+                record.status = IndexStatus::Failed;
+                record.last_error = Some(error.clone());
+                let _ = vector_store.set_document_status(&record);
+                return Ok(IndexResult::failed(document_id, error));
+            }
         };
-        
-        if vector_store.store_chunk(&chunk).is_ok() {
-            chunks_created += 1;
-        }
+        chunks.push(document_chunk(&record, &metadata, index, text_chunk, location, chapter, embedding));
+    }
+    
+    // Swap in the new chunks and registry entry in one transaction
+    record.chunk_count = chunks.len() as i64;
+    record.indexed_at = Some(chrono::Utc::now().to_rfc3339());
+    record.status = IndexStatus::Indexed;
+    if let Err(e) = store.backend().and_then(|backend| backend.store_document(&record, &chunks)) {
+        record.status = IndexStatus::Failed;
+        record.last_error = Some(e.to_string());
+        let _ = vector_store.set_document_status(&record);
+        return Ok(IndexResult::failed(document_id, e.to_string()));
     }
     
     Ok(IndexResult {
         document_id,
-        chunks_created,
-        success: chunks_created > 0,
+        chunks_created: chunks.len(),
+        chunks_reused,
+        success: true,
         error: None,
     })
 }
 
-/// Search indexed documents for relevant context
+/// Build the stored chunk for the `index`th text chunk of a document
+fn document_chunk(
+    record: &DocumentRecord,
+    metadata: &Metadata,
+    index: usize,
+    text_chunk: &TextChunk,
+    location: ChunkLocation,
+    chapter: Option<(usize, Option<&str>)>,
+    embedding: Vec<f32>,
+) -> DocumentChunk {
+    // Section breadcrumbs let results be cited as "Guide > Install"
+    let mut metadata = metadata.clone();
+    if !text_chunk.headings.is_empty() {
+        metadata.insert("headings".to_string(), text_chunk.headings.clone().into());
+    }
+    if let Some((number, title)) = chapter {
+        metadata.insert("chapter_number".to_string(), number.into());
+        if let Some(title) = title {
+            metadata.insert("chapter".to_string(), title.into());
+        }
+    }
+    
+    DocumentChunk {
+        id: chunk_id(&record.collection_id, &record.id, index, &text_chunk.text),
+        collection_id: record.collection_id.clone(),
+        document_id: record.id.clone(),
+        document_name: record.name.clone(),
+        content: text_chunk.text.clone(),
+        embedding,
+        chunk_index: index as i32,
+        token_count: text_chunk.token_count as i32,
+        metadata,
+        location: Some(location),
+    }
+}
+
+/// Search a collection's indexed documents for relevant context.
+///
+/// `top_k` and `similarity_threshold` default to the `max_context_chunks` and
+/// `similarity_threshold` config values. Candidates that fail either limit are
+/// returned in `dropped` together with the reason. `filter` is a metadata
+/// expression such as `tag = "legal" AND modified > 2026-01-01`.
+///
+/// Near-duplicate chunks are always dropped. If a `reranker` is configured
+/// (or `rerank` is set) the top `rerank_top_n` candidates are re-ordered by
+/// it; each result then carries both its cosine `score` and its
+/// `rerank_score`. A failing re-ranker is logged and the retrieval order
+/// kept.
+///
+/// `diversity` (0.0..=1.0, default `search_diversity` from the config) then
+/// re-orders the results with maximal marginal relevance so that overlapping
+/// neighbours don't crowd out other passages.
+///
+/// `query_expansion` ("none", "multi_query", "hyde" or "both"; default from
+/// the config) retrieves with rewritten queries as well as the original and
+/// fuses the rankings. Rewrites are cached per `turn_id`.
 #[tauri::command]
 pub async fn semantic_search(
+    store: State<'_, SharedVectorStore>,
+    expansion_cache: State<'_, QueryExpansionCache>,
     query: String,
     provider: String,
     api_key: String,
     top_k: Option<usize>,
-) -> Result<Vec<SemanticSearchResult>> {
+    similarity_threshold: Option<f32>,
+    collection: Option<String>,
+    filter: Option<String>,
+    diversity: Option<f32>,
+    rerank: Option<bool>,
+    query_expansion: Option<String>,
+    turn_id: Option<String>,
+) -> Result<SemanticSearchResponse> {
+    use crate::config::load_config;
+    use crate::services::diversity;
     use crate::services::embedding::EmbeddingService;
-    use crate::services::vector_store::VectorStore;
+    use crate::services::query_expansion::{self, ExpandedQuery, ExpansionMode, QueryExpander};
+    use crate::services::reranker::Reranker;
+    use crate::services::metadata_filter::MetadataFilter;
+    use crate::services::vector_store::DEFAULT_COLLECTION;
     
-    let k = top_k.unwrap_or(5);
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    let filter = match filter.as_deref().map(str::trim) {
+        Some(expr) if !expr.is_empty() => Some(MetadataFilter::parse(expr)?),
+        _ => None,
+    };
+    let config = load_config();
+    let k = top_k.unwrap_or(config.max_context_chunks as usize);
+    let threshold = similarity_threshold.unwrap_or(config.similarity_threshold);
+    let diversity = diversity.unwrap_or(config.search_diversity).clamp(0.0, 1.0);
     
     // Initialize services
     let embedding_service = EmbeddingService::new(&provider, &api_key, None);
-    let vector_store = VectorStore::new()?;
+    let vector_store = store.get()?;
+    vector_store.ensure_collection(&collection_id)?;
+    
+    // Embed the query and any rewrites of it
+    let mode = ExpansionMode::parse(query_expansion.as_deref().unwrap_or(&config.query_expansion));
+    let cache_key = turn_id.map(|turn| QueryExpansionCache::key(&turn, &query, mode));
+    let queries = match cache_key.as_deref().and_then(|key| expansion_cache.get(key)) {
+        Some(queries) => queries,
+        None => {
+            let mut queries = vec![ExpandedQuery {
+                embedding: embedding_service.embed(&query).await?,
+                text: query.clone(),
+            }];
+            
+            if mode != ExpansionMode::None {
+                let model = if config.query_expansion_model.is_empty() { &config.default_model } else { &config.query_expansion_model };
+                let expander = QueryExpander::new(&provider, &api_key, model);
+                match expander.expand(&query, mode, config.query_expansion_count as usize).await {
+                    Ok(texts) => {
+                        for text in texts {
+                            match embedding_service.embed(&text).await {
+                                Ok(embedding) => queries.push(ExpandedQuery { text, embedding }),
+                                Err(e) => tracing::warn!("Failed to embed expanded query: {}", e),
+                            }
+                        }
+                    }
+                    Err(e) => tracing::warn!("Query expansion failed, searching with the original query: {}", e),
+                }
+            }
+            
+            if let Some(key) = cache_key {
+                expansion_cache.insert(key, queries.clone());
+            }
+            queries
+        }
+    };
+    
+    // Fetch more candidates than we keep so the caller can see what the
+    // threshold and chunk limit removed, and so MMR has alternatives to pick.
+    let candidates = k.saturating_mul(if diversity > 0.0 { 4 } else { 2 });
+    
+    // Search for similar chunks, fusing in BM25 keyword matches if enabled
+    let mut rankings = Vec::with_capacity(queries.len());
+    for expanded in &queries {
+        rankings.push(if config.hybrid_search {
+            vector_store.hybrid_search(&collection_id, &expanded.text, &expanded.embedding, candidates, config.hybrid_keyword_weight, filter.as_ref())?
+        } else {
+            store.backend()?.search(&collection_id, &expanded.embedding, candidates, filter.as_ref())?
+        });
+    }
+    let results = if rankings.len() == 1 {
+        rankings.remove(0)
+    } else {
+        query_expansion::fuse_rankings(rankings, candidates)
+    };
+    
+    let (mut results, duplicates) = diversity::remove_near_duplicates(results);
     
-    // Embed the query
-    let query_embedding = embedding_service.embed(&query).await?;
+    let reranker_kind = match rerank {
+        Some(false) => "none",
+        Some(true) if config.reranker == "none" => "llm",
+        _ => config.reranker.as_str(),
+    };
+    let reranker_model = if config.reranker_model.is_empty() { &config.default_model } else { &config.reranker_model };
+    if let Some(reranker) = Reranker::from_config(reranker_kind, &config.reranker_url, &provider, &api_key, reranker_model) {
+        if let Err(e) = reranker.rerank(&query, &mut results, config.rerank_top_n as usize).await {
+            tracing::warn!("Re-ranking failed, keeping retrieval order: {}", e);
+        }
+    }
+    
+    // Diversify last, so that the re-ranker's order is what MMR trades off
+    // against novelty rather than something it undoes
+    let results = diversity::mmr(results, diversity);
     
-    // Search for similar chunks
-    let results = vector_store.search(&query_embedding, k)?;
+    let mut response = apply_retrieval_filters(
+        results.into_iter().map(SemanticSearchResult::from).collect(),
+        threshold,
+        k,
+    );
+    response.dropped.extend(duplicates.into_iter().map(|r| SemanticSearchResult {
+        filter_reason: Some(FilterReason::Duplicate),
+        ..SemanticSearchResult::from(r)
+    }));
     
-    Ok(results.into_iter().map(|r| SemanticSearchResult {
-        document_name: r.chunk.document_name,
-        content: r.chunk.content,
-        score: r.score,
-        chunk_index: r.chunk.chunk_index,
-    }).collect())
+    Ok(response)
+}
+
+impl From<SearchResult> for SemanticSearchResult {
+    fn from(r: SearchResult) -> Self {
+        Self {
+            document_id: r.chunk.document_id,
+            document_name: r.chunk.document_name,
+            content: r.chunk.content,
+            score: r.score,
+            keyword_score: r.keyword_score,
+            rerank_score: r.rerank_score,
+            chunk_index: r.chunk.chunk_index,
+            metadata: r.chunk.metadata,
+            location: r.chunk.location,
+            filter_reason: None,
+        }
+    }
+}
+
+/// Split ranked candidates into the ones we keep and the ones we drop.
+/// Keyword matches from hybrid search are exempt from the similarity
+/// threshold, since exact identifiers often embed poorly.
+fn apply_retrieval_filters(
+    candidates: Vec<SemanticSearchResult>,
+    similarity_threshold: f32,
+    max_chunks: usize,
+) -> SemanticSearchResponse {
+    let mut results = Vec::new();
+    let mut dropped = Vec::new();
+    
+    for mut candidate in candidates {
+        if candidate.score < similarity_threshold && candidate.keyword_score.is_none() {
+            candidate.filter_reason = Some(FilterReason::BelowThreshold);
+            dropped.push(candidate);
+        } else if results.len() >= max_chunks {
+            candidate.filter_reason = Some(FilterReason::ChunkLimit);
+            dropped.push(candidate);
+        } else {
+            results.push(candidate);
+        }
+    }
+    
+    SemanticSearchResponse {
+        results,
+        dropped,
+        similarity_threshold,
+        max_chunks,
+    }
 }
 
-/// Get relevant context for a chat query using semantic search
+/// Get relevant context for a chat query using semantic search.
+///
+/// With `neighbor_chunks` (default `context_neighbor_chunks` from the config)
+/// each hit is widened with that many chunks before and after it, so the
+/// model sees the surrounding paragraphs. `query_expansion` and `turn_id`
+/// are passed through to `semantic_search`.
 #[tauri::command]
 pub async fn get_relevant_context(
+    store: State<'_, SharedVectorStore>,
+    expansion_cache: State<'_, QueryExpansionCache>,
     query: String,
     provider: String,
     api_key: String,
     max_tokens: Option<usize>,
+    collection: Option<String>,
+    filter: Option<String>,
+    neighbor_chunks: Option<usize>,
+    query_expansion: Option<String>,
+    turn_id: Option<String>,
 ) -> Result<String> {
+    use crate::config::load_config;
+    use crate::services::vector_store::DEFAULT_COLLECTION;
+    
     let max = max_tokens.unwrap_or(4000);
+    let neighbors = neighbor_chunks.unwrap_or(load_config().context_neighbor_chunks as usize);
+    let collection_id = collection.clone().unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    let vector_store = store.get()?;
     
-    // Get top relevant chunks
-    let results = semantic_search(query, provider, api_key, Some(10)).await?;
+    // Get top relevant chunks, limited and thresholded per config
+    let response = semantic_search(
+        store, expansion_cache, query, provider, api_key, None, None, collection, filter, None, None, query_expansion, turn_id,
+    ).await?;
     
-    if results.is_empty() {
+    if response.results.is_empty() {
         return Ok(String::new());
     }
     
-    // Build context from top results, respecting token limit
+    let mut document_chunks = HashMap::new();
+    if neighbors > 0 {
+        for result in &response.results {
+            if !document_chunks.contains_key(&result.document_id) {
+                let chunks = vector_store.get_document_chunks(&collection_id, &result.document_id)?;
+                document_chunks.insert(result.document_id.clone(), chunks);
+            }
+        }
+    }
+    let passages = budget_passages(expand_hits(&response.results, &document_chunks, neighbors), max);
+    
+    // Build context from the passages
     let mut context = String::from("Relevant document context:\n\n");
-    let mut total_tokens = 0;
     
-    for (i, result) in results.iter().enumerate() {
-        let chunk_header = format!("--- {} (relevance: {:.2}) ---\n", result.document_name, result.score);
-        let chunk_tokens = result.content.len() / 4; // rough estimate
-        
-        if total_tokens + chunk_tokens > max {
-            break;
-        }
+    for passage in &passages {
+        // Page numbers let the model cite "page 14"
+        let source = match passage.pages {
+            Some((first, last)) if first == last => format!("{}, page {}", passage.document_name, first),
+            Some((first, last)) => format!("{}, pages {}-{}", passage.document_name, first, last),
+            None => passage.document_name.clone(),
+        };
+        let chunk_header = format!("--- {} (relevance: {:.2}) ---\n", source, passage.score);
         
         context.push_str(&chunk_header);
-        context.push_str(&result.content);
+        context.push_str(&passage.content);
         context.push_str("\n\n");
-        total_tokens += chunk_tokens;
+    }
+    
+    Ok(context)
+}
+
+/// A run of consecutive chunks from one document
+struct ContextPassage {
+    document_id: String,
+    document_name: String,
+    /// Index of the first chunk in the run
+    chunk_index: i32,
+    /// Best score among the hits in the run
+    score: f32,
+    content: String,
+    /// First and last page of the run, for paginated documents
+    pages: Option<(usize, usize)>,
+}
+
+/// Widen each hit to `neighbors` chunks either side, merge windows that
+/// overlap or touch within a document and stitch each window's chunks in
+/// document order. Documents appear in order of their best hit.
+///
+/// `document_chunks` maps each hit's document ID to its chunks sorted by
+/// `chunk_index`; a hit whose document is missing stays a single chunk.
+fn expand_hits(
+    hits: &[SemanticSearchResult],
+    document_chunks: &HashMap<String, Vec<DocumentChunk>>,
+    neighbors: usize,
+) -> Vec<ContextPassage> {
+    let mut documents: Vec<(&str, Vec<&SemanticSearchResult>)> = Vec::new();
+    for hit in hits {
+        match documents.iter_mut().find(|(id, _)| *id == hit.document_id) {
+            Some((_, doc_hits)) => doc_hits.push(hit),
+            None => documents.push((&hit.document_id, vec![hit])),
+        }
+    }
+    
+    let mut passages = Vec::new();
+    for (document_id, mut doc_hits) in documents {
+        let chunks = match document_chunks.get(document_id) {
+            Some(chunks) if neighbors > 0 => chunks,
+            _ => {
+                passages.extend(doc_hits.into_iter().map(|hit| ContextPassage {
+                    document_id: hit.document_id.clone(),
+                    document_name: hit.document_name.clone(),
+                    chunk_index: hit.chunk_index,
+                    score: hit.score,
+                    content: hit.content.clone(),
+                    pages: page_range(hit.location.as_ref(), hit.location.as_ref()),
+                }));
+                continue;
+            }
+        };
         
-        // Only include high-relevance results (score > 0.5)
-        if result.score < 0.5 && i > 2 {
+        // Merge (start, end, score) windows over chunk indexes
+        doc_hits.sort_by_key(|hit| hit.chunk_index);
+        let mut windows: Vec<(i32, i32, f32)> = Vec::new();
+        for hit in &doc_hits {
+            let start = hit.chunk_index - neighbors as i32;
+            let end = hit.chunk_index + neighbors as i32;
+            match windows.last_mut() {
+                Some(last) if start <= last.1 + 1 => {
+                    last.1 = last.1.max(end);
+                    last.2 = last.2.max(hit.score);
+                }
+                _ => windows.push((start, end, hit.score)),
+            }
+        }
+        
+        for (start, end, score) in windows {
+            let window: Vec<&DocumentChunk> = chunks.iter()
+                .filter(|c| c.chunk_index >= start && c.chunk_index <= end)
+                .collect();
+            let mut content = String::new();
+            for chunk in &window {
+                append_chunk(&mut content, &chunk.content);
+            }
+            passages.push(ContextPassage {
+                document_id: document_id.to_string(),
+                document_name: doc_hits[0].document_name.clone(),
+                chunk_index: window.first().map_or(start, |c| c.chunk_index),
+                score,
+                content,
+                pages: page_range(
+                    window.first().and_then(|c| c.location.as_ref()),
+                    window.last().and_then(|c| c.location.as_ref()),
+                ),
+            });
+        }
+    }
+    
+    passages
+}
+
+/// Keep the best passages until `max_tokens` is spent, then order the kept
+/// ones for display: grouped by document, documents in order of their best
+/// passage and each document's passages in document order. A document's
+/// weaker passages don't take the budget from better ones elsewhere.
+fn budget_passages(mut passages: Vec<ContextPassage>, max_tokens: usize) -> Vec<ContextPassage> {
+    passages.sort_by(|a, b| b.score.total_cmp(&a.score));
+    
+    let mut kept = Vec::new();
+    let mut total_tokens = 0;
+    for passage in passages {
+        let tokens = passage.content.len() / 4; // rough estimate
+        if total_tokens + tokens > max_tokens {
             break;
         }
+        total_tokens += tokens;
+        kept.push(passage);
     }
     
-    Ok(context)
+    // Kept best first, so a document's first appearance is its best passage
+    let mut documents: Vec<String> = Vec::new();
+    for passage in &kept {
+        if !documents.contains(&passage.document_id) {
+            documents.push(passage.document_id.clone());
+        }
+    }
+    kept.sort_by_key(|p| (documents.iter().position(|d| *d == p.document_id), p.chunk_index));
+    kept
 }
 
-/// Clear all indexed documents
-#[tauri::command]
-pub async fn clear_index() -> Result<()> {
-    use directories::ProjectDirs;
+/// Pages from the start of the first chunk to the end of the last, if both
+/// have page numbers
+fn page_range(first: Option<&ChunkLocation>, last: Option<&ChunkLocation>) -> Option<(usize, usize)> {
+    Some((first?.page_start?, last?.page_end?))
+}
 
-    if let Some(proj_dirs) = ProjectDirs::from("com", "omnirecall", "OmniRecall") {
-        let db_path = proj_dirs.data_dir().join("vectors.db");
-        if db_path.exists() {
-            std::fs::remove_file(db_path)?;
+/// Append a chunk to a passage, dropping the sentences it repeats from the
+/// end of the previous chunk (chunks are created with overlap)
+fn append_chunk(passage: &mut String, next: &str) {
+    if passage.is_empty() {
+        passage.push_str(next);
+        return;
+    }
+    
+    // Longest prefix of `next` that the passage ends with, on word boundaries
+    let overlap = (1..=next.len().min(passage.len()))
+        .rev()
+        .filter(|&k| next.is_char_boundary(k) && next[k..].chars().next().is_none_or(char::is_whitespace))
+        .find(|&k| {
+            passage.ends_with(&next[..k])
+                && passage[..passage.len() - k].chars().next_back().is_none_or(char::is_whitespace)
+        });
+    
+    match overlap {
+        Some(k) => passage.push_str(&next[k..]),
+        None => {
+            passage.push('\n');
+            passage.push_str(next);
         }
     }
+}
 
-    Ok(())
+/// Clear indexed documents. With a collection only that collection's
+/// documents are removed; without one every collection is emptied.
+#[tauri::command]
+pub async fn clear_index(store: State<'_, SharedVectorStore>, collection: Option<String>) -> Result<()> {
+    let vector_store = store.get()?;
+    
+    match collection {
+        Some(collection_id) => {
+            vector_store.ensure_collection(&collection_id)?;
+            vector_store.clear_collection(&collection_id)
+        }
+        None => vector_store.clear_all(),
+    }
 }
 
-/// Get index statistics
+/// Get index statistics for a collection (the default collection if none is given)
 #[tauri::command]
-pub async fn get_index_stats() -> Result<serde_json::Value> {
-    use crate::services::vector_store::VectorStore;
+pub async fn get_index_stats(
+    store: State<'_, SharedVectorStore>,
+    collection: Option<String>,
+) -> Result<serde_json::Value> {
+    use crate::services::vector_store::DEFAULT_COLLECTION;
+    
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
     
-    match VectorStore::new() {
-        Ok(vs) => {
-            let chunk_count = vs.get_chunk_count().unwrap_or(0);
+    match store.backend().and_then(|backend| backend.stats(&collection_id)) {
+        Ok(stats) => {
             Ok(serde_json::json!({
-                "chunk_count": chunk_count,
-                "indexed": chunk_count > 0,
+                "collection": collection_id,
+                "document_count": stats.document_count,
+                "chunk_count": stats.chunk_count,
+                "indexed": stats.chunk_count > 0,
             }))
         }
         Err(_) => Ok(serde_json::json!({
+            "collection": collection_id,
+            "document_count": 0,
             "chunk_count": 0,
             "indexed": false,
         }))
     }
 }
+
+/// Convert every stored embedding to the configured `embedding_quantization`
+/// and compact the database, so the setting applies to the existing index
+/// without re-embedding it. Returns the number of chunks converted.
+#[tauri::command]
+pub async fn reencode_embeddings(store: State<'_, SharedVectorStore>) -> Result<usize> {
+    store.get()?.reencode_embeddings()
+}
+
+/// List the documents registered in a collection with their index status
+#[tauri::command]
+pub async fn list_indexed_documents(
+    store: State<'_, SharedVectorStore>,
+    collection: Option<String>,
+) -> Result<Vec<DocumentRecord>> {
+    use crate::services::vector_store::DEFAULT_COLLECTION;
+    
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    store.get()?.list_documents(&collection_id)
+}
+
+/// Inspect the chunks stored for an indexed document, in document order
+#[tauri::command]
+pub async fn get_document_chunks(
+    store: State<'_, SharedVectorStore>,
+    document_id: String,
+    collection: Option<String>,
+) -> Result<Vec<ChunkInfo>> {
+    use crate::services::vector_store::DEFAULT_COLLECTION;
+    
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    let vector_store = store.get()?;
+    
+    Ok(vector_store.get_document_chunks(&collection_id, &document_id)?
+        .into_iter()
+        .map(|c| ChunkInfo {
+            id: c.id,
+            chunk_index: c.chunk_index,
+            token_count: c.token_count,
+            content: c.content,
+            metadata: c.metadata,
+        })
+        .collect())
+}
+
+/// Remove a single document and its chunks from the index
+#[tauri::command]
+pub async fn remove_indexed_document(
+    store: State<'_, SharedVectorStore>,
+    document_id: String,
+    collection: Option<String>,
+) -> Result<()> {
+    use crate::services::vector_store::DEFAULT_COLLECTION;
+    
+    let collection_id = collection.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+    let vector_store = store.get()?;
+    
+    if !vector_store.document_exists(&collection_id, &document_id)? {
+        return Err(AppError::Database(format!("Document not indexed: {}", document_id)));
+    }
+    
+    store.backend()?.remove_document(&collection_id, &document_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(score: f32, keyword_score: Option<f32>) -> SemanticSearchResult {
+        SemanticSearchResult {
+            document_id: "doc".to_string(),
+            document_name: "doc.md".to_string(),
+            content: String::new(),
+            score,
+            keyword_score,
+            rerank_score: None,
+            chunk_index: 0,
+            metadata: Metadata::new(),
+            location: None,
+            filter_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_retrieval_filters() {
+        let candidates = vec![
+            candidate(0.9, None),
+            candidate(0.8, None),
+            candidate(0.75, None),
+            candidate(0.4, Some(3.2)),
+            candidate(0.3, None),
+        ];
+
+        let response = apply_retrieval_filters(candidates, 0.7, 2);
+
+        let kept: Vec<f32> = response.results.iter().map(|r| r.score).collect();
+        assert_eq!(kept, vec![0.9, 0.8]);
+
+        let reasons: Vec<_> = response.dropped.iter().map(|r| (r.score, r.filter_reason)).collect();
+        assert_eq!(reasons, vec![
+            (0.75, Some(FilterReason::ChunkLimit)),
+            (0.4, Some(FilterReason::ChunkLimit)),
+            (0.3, Some(FilterReason::BelowThreshold)),
+        ]);
+    }
+
+    #[test]
+    fn test_expand_hits_merges_and_stitches_neighbours() {
+        use crate::services::vector_store::DEFAULT_COLLECTION;
+
+        // Each chunk repeats the last sentence of the one before it
+        let texts = ["S0. S1.", "S1. S2.", "S2. S3.", "S3. S4.", "S4. S5.", "S5. S6.", "S6. S7.", "S7. S8.", "S8. S9."];
+        let chunks: Vec<DocumentChunk> = texts.iter().enumerate().map(|(i, text)| DocumentChunk {
+            id: format!("c{}", i),
+            collection_id: DEFAULT_COLLECTION.to_string(),
+            document_id: "doc".to_string(),
+            document_name: "doc.md".to_string(),
+            content: text.to_string(),
+            embedding: Vec::new(),
+            chunk_index: i as i32,
+            token_count: 2,
+            metadata: Metadata::new(),
+            location: None,
+        }).collect();
+        let document_chunks = HashMap::from([("doc".to_string(), chunks)]);
+
+        let hit = |chunk_index: i32, score: f32| SemanticSearchResult {
+            chunk_index,
+            content: texts[chunk_index as usize].to_string(),
+            ..candidate(score, None)
+        };
+        let hits = vec![hit(3, 0.9), hit(1, 0.8), hit(7, 0.7)];
+
+        let passages = expand_hits(&hits, &document_chunks, 1);
+        let contents: Vec<&str> = passages.iter().map(|p| p.content.as_str()).collect();
+        assert_eq!(contents, vec!["S0. S1. S2. S3. S4. S5.", "S6. S7. S8. S9."]);
+        assert_eq!(passages[0].score, 0.9);
+
+        // Without expansion every hit stays a single chunk, in ranking order
+        let passages = expand_hits(&hits, &document_chunks, 0);
+        assert_eq!(passages.len(), 3);
+        assert_eq!(passages[0].content, "S3. S4.");
+    }
+
+    #[test]
+    fn test_budget_goes_to_the_best_passages() {
+        let passage = |document_id: &str, chunk_index: i32, score: f32| ContextPassage {
+            document_id: document_id.to_string(),
+            document_name: format!("{}.md", document_id),
+            chunk_index,
+            score,
+            content: "x".repeat(400), // 100 tokens
+            pages: None,
+        };
+        let passages = vec![
+            passage("a", 7, 0.9),
+            passage("a", 30, 0.4),
+            passage("a", 2, 0.6),
+            passage("b", 5, 0.8),
+        ];
+
+        // a's weakest passage is dropped rather than b's better one, and the
+        // kept ones are grouped by document in document order
+        let kept: Vec<(String, i32)> = budget_passages(passages, 300)
+            .into_iter()
+            .map(|p| (p.document_id, p.chunk_index))
+            .collect();
+        assert_eq!(kept, vec![("a".to_string(), 2), ("a".to_string(), 7), ("b".to_string(), 5)]);
+    }
+
+    #[test]
+    fn test_book_chapters_are_titled_and_located() {
+        let document = ExtractedDocument::chapters(vec![
+            (None, String::new()),
+            (Some("Ownership".to_string()), "Every value has an owner.".to_string()),
+            (None, "Untitled interlude.".to_string()),
+            (Some("Lifetimes".to_string()), "## Lifetimes\n\nScopes of borrows.".to_string()),
+        ]);
+
+        assert_eq!(
+            document.text,
+            "# Ownership\n\nEvery value has an owner.\n\nUntitled interlude.\n\n## Lifetimes\n\nScopes of borrows."
+        );
+        let owner = document.text.find("owner").unwrap();
+        assert_eq!(document.chapter_at(owner), Some((1, Some("Ownership"))));
+        let interlude = document.text.find("interlude").unwrap();
+        assert_eq!(document.chapter_at(interlude), Some((2, None)));
+        assert_eq!(document.chapter_at(document.text.len() - 1), Some((3, Some("Lifetimes"))));
+    }
+
+    #[tokio::test]
+    async fn test_only_the_preview_is_truncated() {
+        let path = std::env::temp_dir().join(format!("omnirecall-{}-long.md", uuid::Uuid::new_v4()));
+        let text = "Ünïcode line of a long book.\n".repeat(5_000);
+        fs::write(&path, &text).unwrap();
+        let file_path = path.to_string_lossy().into_owned();
+
+        let document = extract_document(&file_path).unwrap();
+        let preview = read_document_content(file_path).await;
+        fs::remove_file(&path).ok();
+
+        assert_eq!(document.text, text);
+        let preview = preview.unwrap();
+        assert!(preview.ends_with("[Content truncated - showing first 100000 characters]"));
+        assert!(text.starts_with(preview.split("...\n\n[Content truncated").next().unwrap()));
+    }
+}
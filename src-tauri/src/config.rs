@@ -1,8 +1,10 @@
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub theme: String,
     pub hotkey: String,
@@ -11,7 +13,48 @@ pub struct AppConfig {
     pub max_context_chunks: u32,
     pub show_token_count: bool,
     pub hybrid_search: bool,
+    /// Weight of the BM25 keyword ranking in hybrid search (0.0 = vector
+    /// only, 1.0 = keyword only). Ignored unless `hybrid_search` is on.
+    pub hybrid_keyword_weight: f32,
     pub similarity_threshold: f32,
+    /// Default MMR diversity for semantic search (0.0 = rank purely by
+    /// relevance, 1.0 = favour novelty as much as relevance)
+    pub search_diversity: f32,
+    /// Re-ranking stage after retrieval: "none", "cross_encoder" (a local
+    /// text-embeddings-inference `/rerank` server at `reranker_url`) or
+    /// "llm" (graded by `reranker_model`, or `default_model` if empty)
+    pub reranker: String,
+    pub reranker_url: String,
+    pub reranker_model: String,
+    /// Number of top candidates passed to the re-ranker
+    pub rerank_top_n: u32,
+    /// Query rewriting before retrieval: "none", "multi_query" (paraphrases),
+    /// "hyde" (a hypothetical answer) or "both". Uses `query_expansion_model`,
+    /// or `default_model` if empty.
+    pub query_expansion: String,
+    pub query_expansion_model: String,
+    /// Number of paraphrases generated by "multi_query"
+    pub query_expansion_count: u32,
+    /// Chunks before and after each hit added to the chat context
+    pub context_neighbor_chunks: u32,
+    /// Chunk size and overlap used when indexing, in tokens
+    pub chunk_size: u32,
+    pub chunk_overlap: u32,
+    /// How documents are chunked: "fixed_size", "sentence", "paragraph",
+    /// "recursive_character", "semantic_boundary" (headings, code
+    /// definitions and CSV rows where the format has them) or "semantic"
+    /// (topic shifts between sentence embeddings)
+    pub chunking_strategy: String,
+    /// Per-extension overrides of `chunking_strategy`, e.g. `{"log": "fixed_size"}`
+    pub chunking_strategies: HashMap<String, String>,
+    /// Smallest chunk the "semantic" strategy cuts at a topic shift, in tokens
+    pub semantic_chunk_min_size: u32,
+    /// Search format for new embeddings: "none" (full-precision f32),
+    /// "int8" or "binary". Quantized formats make searches scan less data
+    /// and keep the full-precision vectors aside for rescoring. Takes effect
+    /// for documents indexed afterwards; the `reencode_embeddings` command
+    /// converts the existing index.
+    pub embedding_quantization: String,
 }
 
 impl Default for AppConfig {
@@ -24,7 +67,23 @@ impl Default for AppConfig {
             max_context_chunks: 5,
             show_token_count: true,
             hybrid_search: false,
+            hybrid_keyword_weight: 0.5,
             similarity_threshold: 0.7,
+            search_diversity: 0.0,
+            reranker: "none".to_string(),
+            reranker_url: String::new(),
+            reranker_model: String::new(),
+            rerank_top_n: 20,
+            query_expansion: "none".to_string(),
+            query_expansion_model: String::new(),
+            query_expansion_count: 3,
+            context_neighbor_chunks: 0,
+            chunk_size: 512,
+            chunk_overlap: 50,
+            chunking_strategy: "semantic_boundary".to_string(),
+            chunking_strategies: HashMap::from([("log".to_string(), "recursive_character".to_string())]),
+            semantic_chunk_min_size: 100,
+            embedding_quantization: "none".to_string(),
         }
     }
 }
@@ -39,6 +98,15 @@ pub fn get_config_path() -> PathBuf {
     get_data_dir().join("config.json")
 }
 
+/// Load the config file, falling back to defaults if it is missing or
+/// unreadable. Missing fields take their default values.
+pub fn load_config() -> AppConfig {
+    std::fs::read_to_string(get_config_path())
+        .ok()
+        .and_then(|c| serde_json::from_str::<AppConfig>(&c).ok())
+        .unwrap_or_default()
+}
+
 #[allow(dead_code)]
 pub fn get_documents_dir() -> PathBuf {
     get_data_dir().join("documents")
@@ -133,6 +133,8 @@ pub fn run() {
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(services::vector_store::SharedVectorStore::default())
+        .manage(services::query_expansion::QueryExpansionCache::default())
         .setup(|app| {
             let window = app.get_webview_window("main").unwrap();
             
@@ -217,6 +219,17 @@ pub fn run() {
             commands::documents::get_relevant_context,
             commands::documents::clear_index,
             commands::documents::get_index_stats,
+            commands::documents::reencode_embeddings,
+            commands::documents::list_indexed_documents,
+            commands::documents::get_document_chunks,
+            commands::documents::remove_indexed_document,
+            commands::collections::create_collection,
+            commands::collections::rename_collection,
+            commands::collections::list_collections,
+            commands::collections::delete_collection,
+            commands::collections::export_collection,
+            commands::collections::inspect_index_archive,
+            commands::collections::import_collection,
             hide_window,
             toggle_dashboard,
             update_hotkey,
@@ -0,0 +1,167 @@
+use crate::services::quantization;
+use crate::services::vector_store::SearchResult;
+
+/// Chunks whose embeddings are at least this similar to a higher-ranked
+/// result are treated as duplicates
+const DUPLICATE_SIMILARITY: f32 = 0.95;
+/// ... as are chunks whose sets of words are at least this similar to one's
+/// (Jaccard index)
+const DUPLICATE_WORD_OVERLAP: f32 = 0.8;
+
+/// Split ranked results into the ones to keep and near-duplicates of a
+/// higher-ranked result. Overlapping chunks of the same passage otherwise
+/// fill the context with the same text several times.
+pub fn remove_near_duplicates(results: Vec<SearchResult>) -> (Vec<SearchResult>, Vec<SearchResult>) {
+    let mut kept: Vec<SearchResult> = Vec::new();
+    let mut duplicates = Vec::new();
+
+    for result in results {
+        let is_duplicate = kept.iter().any(|k| {
+            quantization::dot(&k.chunk.embedding, &result.chunk.embedding) >= DUPLICATE_SIMILARITY
+                || word_overlap(&k.chunk.content, &result.chunk.content) >= DUPLICATE_WORD_OVERLAP
+        });
+        if is_duplicate {
+            duplicates.push(result);
+        } else {
+            kept.push(result);
+        }
+    }
+
+    (kept, duplicates)
+}
+
+/// Re-order results with maximal marginal relevance. Each pick maximises
+/// `(1 - diversity) * relevance - diversity * (max similarity to the picks so far)`,
+/// so `diversity = 0.0` keeps the relevance order and higher values favour
+/// results that cover something new.
+///
+/// Relevance is the re-ranker's score for re-ranked results, the fusion
+/// score (scaled to 0..1) for hybrid results and the cosine similarity
+/// otherwise. Embeddings are unit length, so similarity between results is a
+/// dot product.
+pub fn mmr(results: Vec<SearchResult>, diversity: f32) -> Vec<SearchResult> {
+    let diversity = diversity.clamp(0.0, 1.0);
+    if diversity == 0.0 || results.len() < 3 {
+        return results;
+    }
+
+    let reranked = results.iter().any(|r| r.rerank_score.is_some());
+    let max_fusion = results.iter()
+        .filter_map(|r| r.fusion_score)
+        .fold(0.0f32, f32::max);
+    let relevance = |r: &SearchResult| match (r.rerank_score, r.fusion_score) {
+        (Some(rerank), _) => rerank,
+        // Results beyond the re-ranker's top N stay below the ones it scored
+        (None, _) if reranked => 0.0,
+        (None, Some(fusion)) if max_fusion > 0.0 => fusion / max_fusion,
+        _ => r.score,
+    };
+
+    let mut remaining = results;
+    let mut selected: Vec<SearchResult> = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let (best, _) = remaining.iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let redundancy = selected.iter()
+                    .map(|s| quantization::dot(&s.chunk.embedding, &candidate.chunk.embedding))
+                    .fold(0.0f32, f32::max);
+                (i, (1.0 - diversity) * relevance(candidate) - diversity * redundancy)
+            })
+            .fold((0, f32::NEG_INFINITY), |best, current| if current.1 > best.1 { current } else { best });
+        selected.push(remaining.remove(best));
+    }
+
+    selected
+}
+
+/// Jaccard index of the two texts' distinct words. Symmetric, so a short
+/// chunk whose words all occur in a long one (a heading, say) isn't a
+/// duplicate of it.
+fn word_overlap(a: &str, b: &str) -> f32 {
+    use std::collections::HashSet;
+
+    let words = |text: &str| -> HashSet<String> {
+        text.split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::vector_store::{DocumentChunk, Metadata, DEFAULT_COLLECTION};
+
+    fn result(id: &str, content: &str, embedding: Vec<f32>, score: f32) -> SearchResult {
+        let mut embedding = embedding;
+        quantization::normalize(&mut embedding);
+        SearchResult {
+            chunk: DocumentChunk {
+                id: id.to_string(),
+                collection_id: DEFAULT_COLLECTION.to_string(),
+                document_id: "doc".to_string(),
+                document_name: "doc.md".to_string(),
+                content: content.to_string(),
+                embedding,
+                chunk_index: 0,
+                token_count: 1,
+                metadata: Metadata::new(),
+                location: None,
+            },
+            score,
+            keyword_score: None,
+            fusion_score: None,
+            rerank_score: None,
+        }
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.chunk.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_remove_near_duplicates() {
+        let results = vec![
+            result("a", "the retry policy backs off exponentially", vec![1.0, 0.0, 0.0], 0.9),
+            result("b", "The retry policy backs off exponentially.", vec![0.7, 0.7, 0.0], 0.85),
+            result("c", "timeouts are configured per request", vec![0.99, 0.05, 0.0], 0.8),
+            result("d", "logging goes to stderr", vec![0.0, 0.0, 1.0], 0.5),
+            result("e", "Retry policy", vec![0.0, 1.0, 0.0], 0.4),
+        ];
+
+        let (kept, duplicates) = remove_near_duplicates(results);
+        // "e" only has words of "a", but is far shorter
+        assert_eq!(ids(&kept), vec!["a", "d", "e"]);
+        // "b" repeats the words of "a"; "c" has a near-identical embedding
+        assert_eq!(ids(&duplicates), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_mmr_prefers_novel_results() {
+        let results = || vec![
+            result("a", "a", vec![1.0, 0.1, 0.0], 0.95),
+            result("a2", "a2", vec![1.0, 0.15, 0.0], 0.94),
+            result("b", "b", vec![0.3, 0.0, 1.0], 0.80),
+        ];
+
+        assert_eq!(ids(&mmr(results(), 0.0)), vec!["a", "a2", "b"]);
+        assert_eq!(ids(&mmr(results(), 0.5)), vec!["a", "b", "a2"]);
+
+        // Re-ranker scores take precedence over the cosine scores
+        let mut reranked = results();
+        for (result, score) in reranked.iter_mut().zip([0.5, 0.9, 0.8]) {
+            result.rerank_score = Some(score);
+        }
+        assert_eq!(ids(&mmr(reranked, 0.5)), vec!["a2", "b", "a"]);
+    }
+}
@@ -1,10 +1,125 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::services::vector_store::ChunkLocation;
+
 pub struct DocumentPipeline;
 
 #[derive(Debug, Clone)]
 pub struct TextChunk {
-    pub id: String,
     pub text: String,
     pub token_count: usize,
+    /// Enclosing section headings (or code definitions), outermost first
+    pub headings: Vec<String>,
+    /// Byte range of the source text the chunk was taken from. Chunks of
+    /// structured formats can differ from that slice in whitespace, and the
+    /// header row repeated in CSV chunks lies outside it.
+    pub span: Range<usize>,
+}
+
+/// Source structure a document is chunked along
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    /// Prose with no usable structure; packed sentence by sentence
+    Plain,
+    /// Split at `#` headings, keeping code fences and tables whole
+    Markdown,
+    /// Split at underlined (and overlined) section titles
+    Rst,
+    /// Split at top-level definitions, then at nested ones
+    Code,
+    /// Groups of rows, each repeating the header row
+    Csv,
+}
+
+impl DocumentFormat {
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_ascii_lowercase().as_str() {
+            // Office documents, books and web pages are extracted as Markdown
+            "md" | "markdown" | "docx" | "pptx" | "xlsx" | "odt" | "epub" | "html" | "htm" => DocumentFormat::Markdown,
+            "rst" => DocumentFormat::Rst,
+            "csv" => DocumentFormat::Csv,
+            "py" | "js" | "ts" | "tsx" | "jsx" | "rs" | "java" | "cpp" | "c" | "h" | "hpp"
+            | "go" | "rb" | "php" | "swift" | "kt" | "cs" | "sh" | "ps1" | "sql" => DocumentFormat::Code,
+            _ => DocumentFormat::Plain,
+        }
+    }
+}
+
+/// How a document's text is cut into chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChunkingStrategy {
+    /// Runs of words up to the chunk size, ignoring sentence structure
+    FixedSize,
+    /// Whole sentences packed up to the chunk size
+    Sentence,
+    /// Whole paragraphs packed up to the chunk size; longer paragraphs are
+    /// split into sentences
+    Paragraph,
+    /// Split at paragraph breaks, then line breaks, then between words and
+    /// finally characters until every piece fits, then pack the pieces
+    RecursiveCharacter,
+    /// The document's own boundaries: headings, code definitions or CSV row
+    /// groups, depending on its `DocumentFormat`
+    #[default]
+    SemanticBoundary,
+    /// Sentences grouped by topic, split where the embeddings of
+    /// neighbouring sentences diverge (see `semantic_chunker`)
+    Semantic,
+}
+
+impl ChunkingStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fixed_size" => Some(ChunkingStrategy::FixedSize),
+            "sentence" => Some(ChunkingStrategy::Sentence),
+            "paragraph" => Some(ChunkingStrategy::Paragraph),
+            "recursive_character" => Some(ChunkingStrategy::RecursiveCharacter),
+            "semantic_boundary" => Some(ChunkingStrategy::SemanticBoundary),
+            "semantic" => Some(ChunkingStrategy::Semantic),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChunkingStrategy::FixedSize => "fixed_size",
+            ChunkingStrategy::Sentence => "sentence",
+            ChunkingStrategy::Paragraph => "paragraph",
+            ChunkingStrategy::RecursiveCharacter => "recursive_character",
+            ChunkingStrategy::SemanticBoundary => "semantic_boundary",
+            ChunkingStrategy::Semantic => "semantic",
+        }
+    }
+}
+
+/// Words ending in a period that don't end a sentence (lowercase). Single
+/// letters followed by a period (initials, "z. B.") are handled separately.
+const ABBREVIATIONS: &[&str] = &[
+    // English
+    "mr.", "mrs.", "ms.", "dr.", "prof.", "sr.", "jr.", "st.", "vs.", "e.g.", "i.e.", "cf.",
+    // German
+    "z.b.", "bzw.", "vgl.", "u.a.", "d.h.", "ggf.", "evtl.", "inkl.", "zzgl.", "bzgl.", "sog.", "hr.",
+];
+
+/// Abbreviations that are also ordinary words or often end a sentence ("The
+/// answer is no."). They only hold the sentence together when a number or
+/// lowercase word follows ("no. 5", "Acme Inc. announced").
+const AMBIGUOUS_ABBREVIATIONS: &[&str] = &[
+    // English
+    "approx.", "fig.", "no.", "vol.", "ch.", "sec.", "inc.", "ltd.", "co.", "corp.", "dept.", "est.",
+    "jan.", "feb.", "mar.", "apr.", "jun.", "jul.", "aug.", "sep.", "sept.", "oct.", "nov.", "dec.",
+    // German
+    "usw.", "ca.", "nr.", "fr.", "str.", "abs.", "s.o.", "s.u.",
+];
+
+/// Longest code signature kept as a breadcrumb, in characters
+const MAX_SIGNATURE_CHARS: usize = 120;
+
+/// A heading and the text under it, up to the next heading of any level
+struct Section<'a> {
+    headings: Vec<String>,
+    /// The section's lines, as slices of the source text
+    lines: Vec<&'a str>,
 }
 
 impl DocumentPipeline {
@@ -12,77 +127,357 @@ impl DocumentPipeline {
         Self
     }
 
+    /// Chunk a document along its structure. A chunk never spans two
+    /// sections, and never splits a code definition that fits in one chunk.
+    pub fn chunk_document(&self, text: &str, format: DocumentFormat, chunk_size: usize, overlap: usize) -> Vec<TextChunk> {
+        match format {
+            DocumentFormat::Plain => self.chunk_text(text, chunk_size, overlap),
+            DocumentFormat::Markdown => self.chunk_sections(text, markdown_sections(text), chunk_size, overlap),
+            DocumentFormat::Rst => self.chunk_sections(text, rst_sections(text), chunk_size, overlap),
+            DocumentFormat::Code => {
+                let lines: Vec<&str> = text.lines().collect();
+                let mut chunks = Vec::new();
+                self.chunk_code(text, &lines, &[], chunk_size, &mut chunks);
+                chunks
+            }
+            DocumentFormat::Csv => self.chunk_csv(text, chunk_size),
+        }
+    }
+
+    /// Cut a document into chunks with `strategy`. `format` only matters for
+    /// `SemanticBoundary`.
+    pub fn chunk(
+        &self,
+        text: &str,
+        strategy: ChunkingStrategy,
+        format: DocumentFormat,
+        chunk_size: usize,
+        overlap: usize,
+    ) -> Vec<TextChunk> {
+        if strategy == ChunkingStrategy::SemanticBoundary {
+            return self.chunk_document(text, format, chunk_size, overlap);
+        }
+
+        self.chunk_ranges(text, strategy, chunk_size, overlap)
+            .into_iter()
+            .map(|range| self.make_chunk(text[range.clone()].to_string(), range, Vec::new()))
+            .collect()
+    }
+
+    /// Pack sentences into chunks of at most `chunk_size` tokens. Each chunk
+    /// after the first starts with the previous chunk's last sentences, up to
+    /// `overlap` tokens of them. Sentences longer than a chunk are split
+    /// between words (or, for a single huge word, between characters).
     pub fn chunk_text(&self, text: &str, chunk_size: usize, overlap: usize) -> Vec<TextChunk> {
+        self.chunk(text, ChunkingStrategy::Sentence, DocumentFormat::Plain, chunk_size, overlap)
+    }
+
+    /// Byte ranges of the chunks `strategy` cuts `text` into. Chunks keep
+    /// the whitespace between their pieces as it was in the source.
+    /// `SemanticBoundary` has no structure to follow here, and `Semantic`
+    /// needs embeddings (see `semantic_chunker`); both pack sentences.
+    fn chunk_ranges(&self, text: &str, strategy: ChunkingStrategy, chunk_size: usize, overlap: usize) -> Vec<Range<usize>> {
+        let chunk_size = chunk_size.max(1);
+        // An overlap of at most half a chunk leaves room for new text in every chunk
+        let overlap = overlap.min(chunk_size / 2);
+        let tokens = |range: Range<usize>| self.estimate_tokens(&text[range]);
+
+        let pieces: Vec<Range<usize>> = match strategy {
+            ChunkingStrategy::FixedSize => words(text, 0..text.len(), chunk_size * 4),
+            ChunkingStrategy::Sentence | ChunkingStrategy::SemanticBoundary | ChunkingStrategy::Semantic => {
+                self.sentence_ranges(text, chunk_size)
+            }
+            ChunkingStrategy::Paragraph => split_at(text, 0..text.len(), &paragraph_break())
+                .into_iter()
+                .flat_map(|paragraph| {
+                    if tokens(paragraph.clone()) <= chunk_size {
+                        return vec![paragraph];
+                    }
+                    self.split_sentences(&text[paragraph.clone()])
+                        .into_iter()
+                        .map(|s| paragraph.start + s.start..paragraph.start + s.end)
+                        .flat_map(|sentence| self.split_oversize(text, sentence, chunk_size))
+                        .collect()
+                })
+                .collect(),
+            ChunkingStrategy::RecursiveCharacter => {
+                let separators = [paragraph_break(), regex::Regex::new(r"\n").expect("valid regex")];
+                self.split_recursively(text, 0..text.len(), &separators, chunk_size)
+            }
+        };
+
+        let mut ranges = Vec::new();
+        let mut start = 0;
+
+        while start < pieces.len() {
+            let mut end = start + 1;
+            while end < pieces.len() && tokens(pieces[start].start..pieces[end].end) <= chunk_size {
+                end += 1;
+            }
+            ranges.push(pieces[start].start..pieces[end - 1].end);
+            if end == pieces.len() {
+                break;
+            }
+
+            // Carry trailing pieces over while they fit the overlap and
+            // still leave room for the next piece
+            let mut next = end;
+            while next > start + 1
+                && tokens(pieces[next - 1].start..pieces[end - 1].end) <= overlap
+                && tokens(pieces[next - 1].start..pieces[end].end) <= chunk_size
+            {
+                next -= 1;
+            }
+            start = next;
+        }
+
+        ranges
+    }
+
+    /// Split at the first separator, and split pieces that are still too
+    /// large at the next one, ending with words and then characters
+    fn split_recursively(&self, text: &str, range: Range<usize>, separators: &[regex::Regex], chunk_size: usize) -> Vec<Range<usize>> {
+        match separators.split_first() {
+            Some(_) if self.estimate_tokens(&text[range.clone()]) <= chunk_size => trim_range(text, range).into_iter().collect(),
+            Some((separator, rest)) => split_at(text, range, separator)
+                .into_iter()
+                .flat_map(|piece| self.split_recursively(text, piece, rest, chunk_size))
+                .collect(),
+            None => self.split_oversize(text, range, chunk_size),
+        }
+    }
+
+    /// Byte ranges of the sentences in `text`, with sentences longer than
+    /// `max_tokens` split
+    pub fn sentence_ranges(&self, text: &str, max_tokens: usize) -> Vec<Range<usize>> {
+        self.split_sentences(text)
+            .into_iter()
+            .flat_map(|sentence| self.split_oversize(text, sentence, max_tokens.max(1)))
+            .collect()
+    }
+
+    /// Split `range` of `text` into pieces of at most `chunk_size` tokens,
+    /// between words where possible
+    fn split_oversize(&self, text: &str, range: Range<usize>, chunk_size: usize) -> Vec<Range<usize>> {
+        if self.estimate_tokens(&text[range.clone()]) <= chunk_size {
+            return vec![range];
+        }
+
+        let mut pieces: Vec<Range<usize>> = Vec::new();
+        for word in words(text, range, chunk_size * 4) {
+            match pieces.last_mut() {
+                Some(piece) if self.estimate_tokens(&text[piece.start..word.end]) <= chunk_size => piece.end = word.end,
+                _ => pieces.push(word),
+            }
+        }
+
+        pieces
+    }
+
+    /// Pack each section's blocks (paragraphs, lists, tables, code fences)
+    /// into chunks. Prose blocks too large for one chunk fall back to
+    /// sentence packing; tables and code fences are split between lines.
+    fn chunk_sections(&self, source: &str, sections: Vec<Section>, chunk_size: usize, overlap: usize) -> Vec<TextChunk> {
         let mut chunks = Vec::new();
-        let sentences = self.split_sentences(text);
-        
-        let mut current_chunk = String::new();
-        let mut current_tokens = 0;
-        
-        for (i, sentence) in sentences.iter().enumerate() {
-            let sentence_tokens = self.estimate_tokens(sentence);
-            
-            if current_tokens + sentence_tokens > chunk_size && !current_chunk.is_empty() {
-                // Save current chunk
-                chunks.push(TextChunk {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    text: current_chunk.trim().to_string(),
-                    token_count: current_tokens,
-                });
-                
-                // Start new chunk with overlap
-                current_chunk = String::new();
-                current_tokens = 0;
-                
-                // Add overlap from previous sentences
-                let overlap_start = i.saturating_sub(overlap / 50);
-                for sentence_item in sentences.iter().take(i).skip(overlap_start) {
-                    current_chunk.push_str(sentence_item);
-                    current_chunk.push(' ');
-                    current_tokens += self.estimate_tokens(sentence_item);
+
+        for section in sections {
+            let mut pieces = Vec::new();
+            for block in markdown_blocks(&section.lines) {
+                let (text, span) = join_lines(source, block);
+                if self.estimate_tokens(&text) <= chunk_size {
+                    pieces.push((text, span));
+                } else if is_verbatim_block(&text) {
+                    let lines = block.iter().map(|line| (line.to_string(), span_of(source, line))).collect();
+                    pieces.extend(self.pack(lines, "\n", chunk_size));
+                } else {
+                    let sentences = self.chunk_text(&text, chunk_size, overlap);
+                    pieces.extend(sentences.into_iter().map(|c| (c.text, sub_span(&text, &span, c.span))));
                 }
             }
-            
-            current_chunk.push_str(sentence);
-            current_chunk.push(' ');
-            current_tokens += sentence_tokens;
-        }
-        
-        // Don't forget the last chunk
-        if !current_chunk.trim().is_empty() {
-            chunks.push(TextChunk {
-                id: uuid::Uuid::new_v4().to_string(),
-                text: current_chunk.trim().to_string(),
-                token_count: current_tokens,
-            });
+
+            for (text, span) in self.pack(pieces, "\n\n", chunk_size) {
+                chunks.push(self.make_chunk(text, span, section.headings.clone()));
+            }
         }
 
         chunks
     }
 
-    fn split_sentences(&self, text: &str) -> Vec<String> {
-        // Simple sentence splitting
-        let mut sentences = Vec::new();
-        let mut current = String::new();
-        
-        for c in text.chars() {
-            current.push(c);
-            if c == '.' || c == '!' || c == '?' || c == '\n' {
-                if !current.trim().is_empty() {
-                    sentences.push(current.trim().to_string());
+    /// Chunk source lines at definition boundaries. Definitions that fit are
+    /// packed together; larger ones are split at their nested definitions,
+    /// with the enclosing definition's signature added to the breadcrumbs.
+    fn chunk_code(&self, source: &str, lines: &[&str], headings: &[String], chunk_size: usize, chunks: &mut Vec<TextChunk>) {
+        let Some(level) = code_level(lines) else {
+            return;
+        };
+
+        let mut pending: Vec<&str> = Vec::new();
+        for unit in code_units(lines, level) {
+            if self.estimate_tokens(&unit.join("\n")) <= chunk_size {
+                let combined = pending.iter().chain(unit).copied().collect::<Vec<_>>().join("\n");
+                if !pending.is_empty() && self.estimate_tokens(&combined) > chunk_size {
+                    self.flush_code(source, &mut pending, headings, chunks);
+                }
+                pending.extend_from_slice(unit);
+                continue;
+            }
+
+            self.flush_code(source, &mut pending, headings, chunks);
+            let body_start = unit.iter()
+                .position(|l| !l.trim().is_empty() && !is_closing_line(l) && indentation(l) > level)
+                .unwrap_or(0);
+            if body_start == 0 {
+                // No nested definitions to split at
+                let lines = unit.iter().map(|line| (line.to_string(), span_of(source, line))).collect();
+                for (text, span) in self.pack(lines, "\n", chunk_size) {
+                    chunks.push(self.make_chunk(text, span, headings.to_vec()));
+                }
+                continue;
+            }
+
+            let mut breadcrumbs = headings.to_vec();
+            breadcrumbs.extend(code_signature(unit));
+            let header = unit[..body_start].join("\n");
+            let Some(nested_size) = self.budget_after_header(&header, chunk_size) else {
+                // A header this large (long doc comments, say) gets chunks
+                // of its own
+                let lines = unit[..body_start].iter().map(|line| (line.to_string(), span_of(source, line))).collect();
+                for (text, span) in self.pack(lines, "\n", chunk_size) {
+                    chunks.push(self.make_chunk(text, span, breadcrumbs.clone()));
                 }
-                current = String::new();
+                self.chunk_code(source, &unit[body_start..], &breadcrumbs, chunk_size, chunks);
+                continue;
+            };
+
+            // Keep the signature with the first nested chunk, leaving room
+            // for it in the nested chunks
+            let first = chunks.len();
+            self.chunk_code(source, &unit[body_start..], &breadcrumbs, nested_size, chunks);
+            if let Some(chunk) = chunks.get_mut(first) {
+                chunk.text = format!("{}\n{}", header, chunk.text);
+                chunk.token_count = self.estimate_tokens(&chunk.text);
+                chunk.span.start = span_of(source, unit[0]).start;
             }
         }
-        
+        self.flush_code(source, &mut pending, headings, chunks);
+    }
+
+    fn flush_code(&self, source: &str, pending: &mut Vec<&str>, headings: &[String], chunks: &mut Vec<TextChunk>) {
+        let lines = std::mem::take(pending);
+        let Some(level) = code_level(&lines) else {
+            return;
+        };
+
+        // A chunk holding a single definition is labelled with it
+        let mut breadcrumbs = headings.to_vec();
+        let units = code_units(&lines, level);
+        if let [unit] = units.as_slice() {
+            breadcrumbs.extend(code_signature(unit));
+        }
+        let (text, span) = join_lines(source, &lines);
+        chunks.push(self.make_chunk(text, span, breadcrumbs));
+    }
+
+    /// Groups of CSV records, each starting with the header row so that
+    /// every chunk can be read on its own. A header taking up more than half
+    /// a chunk is only kept at the start of the first.
+    fn chunk_csv(&self, text: &str, chunk_size: usize) -> Vec<TextChunk> {
+        let mut records = csv_records(text);
+        if records.is_empty() {
+            return Vec::new();
+        }
+        let header = records[0].0.clone();
+        let Some(budget) = self.budget_after_header(&header, chunk_size).filter(|_| records.len() > 1) else {
+            return self.pack(records, "\n", chunk_size)
+                .into_iter()
+                .map(|(group, span)| self.make_chunk(group, span, Vec::new()))
+                .collect();
+        };
+
+        records.remove(0);
+        self.pack(records, "\n", budget)
+            .into_iter()
+            .map(|(group, span)| self.make_chunk(format!("{}\n{}", header, group), span, Vec::new()))
+            .collect()
+    }
+
+    /// Tokens left for content when `header` and a line break start every
+    /// chunk, or `None` if the header would take up more than half of it
+    fn budget_after_header(&self, header: &str, chunk_size: usize) -> Option<usize> {
+        let budget = chunk_size.checked_sub(self.estimate_tokens(header) + 1)?;
+        (budget > 0 && budget >= chunk_size / 2).then_some(budget)
+    }
+
+    /// Join pieces (text and source span) with `separator` into texts of at
+    /// most `chunk_size` tokens. Pieces larger than that are split first.
+    fn pack(&self, pieces: Vec<(String, Range<usize>)>, separator: &str, chunk_size: usize) -> Vec<(String, Range<usize>)> {
+        let mut packed = Vec::new();
+        let mut current = String::new();
+        let mut current_span: Option<Range<usize>> = None;
+
+        let pieces = pieces.into_iter().flat_map(|(text, span)| {
+            self.split_oversize(&text, 0..text.len(), chunk_size.max(1))
+                .into_iter()
+                .map(|range| (text[range.clone()].to_string(), sub_span(&text, &span, range)))
+                .collect::<Vec<_>>()
+        });
+        for (piece, span) in pieces {
+            if !current.is_empty()
+                && self.estimate_tokens(&current) + self.estimate_tokens(separator) + self.estimate_tokens(&piece) > chunk_size
+            {
+                packed.push((std::mem::take(&mut current), current_span.take().unwrap_or_default()));
+            }
+            if !current.is_empty() {
+                current.push_str(separator);
+            }
+            current.push_str(&piece);
+            current_span = Some(match current_span {
+                Some(current_span) => current_span.start..span.end,
+                None => span,
+            });
+        }
         if !current.trim().is_empty() {
-            sentences.push(current.trim().to_string());
+            packed.push((current, current_span.unwrap_or_default()));
+        }
+
+        packed
+    }
+
+    pub fn make_chunk(&self, text: String, span: Range<usize>, headings: Vec<String>) -> TextChunk {
+        let text = text.trim_end().to_string();
+        TextChunk {
+            token_count: self.estimate_tokens(&text),
+            text,
+            headings,
+            span,
+        }
+    }
+
+    /// Byte ranges of the sentences (and lines) in `text`, without their
+    /// surrounding whitespace.
+    ///
+    /// Uses Unicode sentence boundaries (UAX #29), which already keep
+    /// "3.14", "v1.2.0" and "e.g. the" together and split after CJK
+    /// punctuation such as 。 and ！. Boundaries after a known abbreviation
+    /// or an initial ("Dr. Smith", "J. R. R. Tolkien") are then dropped.
+    fn split_sentences(&self, text: &str) -> Vec<Range<usize>> {
+        let mut sentences = Vec::new();
+        let mut start = None;
+        
+        for (offset, segment) in text.split_sentence_bound_indices() {
+            let sentence_start = *start.get_or_insert(offset);
+            let end = offset + segment.len();
+            if end < text.len() && !segment.contains('\n') && ends_with_abbreviation(&text[sentence_start..end], &text[end..]) {
+                continue;
+            }
+            sentences.extend(trim_range(text, sentence_start..end));
+            start = None;
         }
         
         sentences
     }
 
-    fn estimate_tokens(&self, text: &str) -> usize {
+    pub fn estimate_tokens(&self, text: &str) -> usize {
         // Rough estimate: ~4 characters per token for English
         text.len().div_ceil(4)
     }
@@ -94,3 +489,731 @@ impl Default for DocumentPipeline {
         Self::new()
     }
 }
+
+/// Stable ID for the chunk at `index` of a document. Re-indexing unchanged
+/// text yields the same IDs, so citations and anything else keyed by chunk
+/// ID survive a re-index; a chunk whose text or position changed gets a
+/// new one.
+pub fn chunk_id(collection_id: &str, document_id: &str, index: usize, text: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for part in [collection_id.as_bytes(), document_id.as_bytes(), &(index as u64).to_le_bytes()] {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    hasher.update(Sha256::digest(text.as_bytes()));
+
+    let digest = hasher.finalize();
+    uuid::Uuid::from_slice(&digest[..16]).expect("16 bytes").to_string()
+}
+
+/// Maps byte offsets in a document's text to character offsets, lines and,
+/// for paginated documents, pages
+pub struct SourceMap<'a> {
+    text: &'a str,
+    /// Byte offset at which each line starts, and its character offset
+    lines: Vec<(usize, usize)>,
+    page_starts: &'a [usize],
+}
+
+impl<'a> SourceMap<'a> {
+    /// `page_starts` holds the byte offset at which each page starts; it is
+    /// empty for documents without pages.
+    pub fn new(text: &'a str, page_starts: &'a [usize]) -> Self {
+        let mut lines = vec![(0, 0)];
+        for (chars, (offset, c)) in text.char_indices().enumerate() {
+            if c == '\n' {
+                lines.push((offset + 1, chars + 1));
+            }
+        }
+
+        Self { text, lines, page_starts }
+    }
+
+    /// Location of a chunk taken from `span` of the text
+    pub fn locate(&self, span: &Range<usize>) -> ChunkLocation {
+        let last = span.end.max(span.start + 1) - 1;
+        let page = |offset: usize| {
+            (!self.page_starts.is_empty()).then(|| self.page_starts.partition_point(|&start| start <= offset).max(1))
+        };
+
+        ChunkLocation {
+            char_start: self.char_offset(span.start),
+            char_end: self.char_offset(span.end),
+            line_start: self.line(span.start),
+            line_end: self.line(last),
+            page_start: page(span.start),
+            page_end: page(last),
+        }
+    }
+
+    /// Line (counting from 1) containing byte `offset`
+    fn line(&self, offset: usize) -> usize {
+        self.lines.partition_point(|&(start, _)| start <= offset)
+    }
+
+    fn char_offset(&self, offset: usize) -> usize {
+        let (start, chars) = self.lines[self.line(offset) - 1];
+        chars + self.text[start..offset].chars().count()
+    }
+}
+
+/// Whether `sentence` ends with an abbreviation or initial rather than a
+/// real full stop, given the text `following` it
+fn ends_with_abbreviation(sentence: &str, following: &str) -> bool {
+    let mut words = sentence.split_whitespace().rev();
+    let Some(word) = words.next() else {
+        return false;
+    };
+    let word = word.trim_start_matches(['(', '[', '"', '\'', '“', '‘']);
+    let Some(stem) = word.strip_suffix('.') else {
+        return false;
+    };
+    let lowercase = word.to_lowercase();
+    let next = following.split_whitespace().next().unwrap_or_default();
+    
+    if ABBREVIATIONS.contains(&lowercase.as_str()) {
+        return true;
+    }
+    if AMBIGUOUS_ABBREVIATIONS.contains(&lowercase.as_str()) {
+        return next.starts_with(|c: char| c.is_lowercase() || c.is_ascii_digit());
+    }
+    
+    // Initials. A run of them holds together ("J. R. R. Tolkien", "z. B."),
+    // and so does a single one between capitalised names ("Harry S.
+    // Truman"), but not one after an ordinary word ("plan B. Then").
+    let is_initial = |word: &str| {
+        let mut chars = word.chars();
+        matches!((chars.next(), chars.next(), chars.next()), (Some(c), Some('.'), None) if c.is_alphabetic())
+    };
+    let starts_uppercase = |word: &str| word.starts_with(char::is_uppercase);
+    if !is_initial(word) {
+        return false;
+    }
+    is_initial(next)
+        || (starts_uppercase(stem) && starts_uppercase(next) && words.next().is_none_or(starts_uppercase))
+}
+
+/// Words in `range` of `text`, with any longer than `max_bytes` cut at
+/// character boundaries
+fn words(text: &str, range: Range<usize>, max_bytes: usize) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    let chars = text[range.clone()].char_indices().map(|(i, c)| (range.start + i, c));
+
+    for (i, c) in chars.chain([(range.end, ' ')]) {
+        match (c.is_whitespace(), word_start) {
+            (false, None) => word_start = Some(i),
+            (true, Some(mut start)) => {
+                while i - start > max_bytes {
+                    let mut split = start + max_bytes;
+                    while !text.is_char_boundary(split) {
+                        split -= 1;
+                    }
+                    words.push(start..split);
+                    start = split;
+                }
+                words.push(start..i);
+                word_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// A blank line, possibly containing spaces
+fn paragraph_break() -> regex::Regex {
+    regex::Regex::new(r"\n[ \t\r]*\n").expect("valid regex")
+}
+
+/// The trimmed, non-empty pieces of `range` between matches of `separator`
+fn split_at(text: &str, range: Range<usize>, separator: &regex::Regex) -> Vec<Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = range.start;
+
+    for found in separator.find_iter(&text[range.clone()]) {
+        pieces.extend(trim_range(text, start..range.start + found.start()));
+        start = range.start + found.end();
+    }
+    pieces.extend(trim_range(text, start..range.end));
+
+    pieces
+}
+
+/// `range` without leading and trailing whitespace, or `None` if that
+/// leaves nothing
+fn trim_range(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.start + slice.trim_end().len();
+    (start < end).then_some(start..end)
+}
+
+/// Split Markdown at ATX headings (`#` to `######`), ignoring `#` lines
+/// inside code fences. Each section's body starts with its heading line.
+fn markdown_sections(text: &str) -> Vec<Section<'_>> {
+    let mut sections = vec![Section { headings: Vec::new(), lines: Vec::new() }];
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if let Some(marker) = fence_marker(trimmed) {
+            fence = Some(marker);
+        } else if let Some((level, title)) = atx_heading(line) {
+            while stack.last().is_some_and(|(l, _)| *l >= level) {
+                stack.pop();
+            }
+            stack.push((level, title));
+            sections.push(Section {
+                headings: stack.iter().map(|(_, t)| t.clone()).collect(),
+                lines: Vec::new(),
+            });
+        }
+
+        sections.last_mut().expect("at least one section").lines.push(line);
+    }
+
+    sections.retain(|s| s.lines.iter().any(|l| !l.trim().is_empty()));
+    sections
+}
+
+/// Split reStructuredText at section titles. RST has no fixed heading
+/// levels: each adornment style (underline character, with or without an
+/// overline) takes the next level the first time it appears.
+fn rst_sections(text: &str) -> Vec<Section<'_>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut sections = vec![Section { headings: Vec::new(), lines: Vec::new() }];
+    let mut styles: Vec<(char, bool)> = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut i = 0;
+
+    let title_at = |i: usize| lines.get(i).map(|l| l.trim()).filter(|l| !l.is_empty() && rst_adornment(l).is_none());
+    let adornment_at = |i: usize| lines.get(i).and_then(|l| rst_adornment(l)).map(|c| (c, lines[i].trim().chars().count()));
+
+    while i < lines.len() {
+        let overlined = match (adornment_at(i), title_at(i + 1), adornment_at(i + 2)) {
+            (Some((over, width)), Some(title), Some((under, _))) if over == under && width >= title.chars().count() => {
+                Some((title, (under, true), 3))
+            }
+            _ => None,
+        };
+        let heading = overlined.or_else(|| match (title_at(i), adornment_at(i + 1)) {
+            (Some(title), Some((under, width))) if width >= title.chars().count() => Some((title, (under, false), 2)),
+            _ => None,
+        });
+
+        let Some((title, style, consumed)) = heading else {
+            sections.last_mut().expect("at least one section").lines.push(lines[i]);
+            i += 1;
+            continue;
+        };
+
+        let level = match styles.iter().position(|s| *s == style) {
+            Some(level) => level,
+            None => {
+                styles.push(style);
+                styles.len() - 1
+            }
+        };
+        while stack.last().is_some_and(|(l, _)| *l >= level) {
+            stack.pop();
+        }
+        stack.push((level, title.to_string()));
+        sections.push(Section {
+            headings: stack.iter().map(|(_, t)| t.clone()).collect(),
+            lines: vec![title],
+        });
+        i += consumed;
+    }
+
+    sections.retain(|s| s.lines.iter().any(|l| !l.trim().is_empty()));
+    sections
+}
+
+/// The character of an RST adornment line such as `=====`, if it is one
+fn rst_adornment(line: &str) -> Option<char> {
+    let line = line.trim_end();
+    let first = line.chars().next()?;
+    let is_adornment = line.chars().count() >= 3
+        && "=-~^\"'`#*+_.:".contains(first)
+        && line.chars().all(|c| c == first);
+    is_adornment.then_some(first)
+}
+
+fn atx_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    Some((level, rest.trim().trim_end_matches('#').trim_end().to_string()))
+}
+
+fn fence_marker(line: &str) -> Option<&'static str> {
+    ["```", "~~~"].into_iter().find(|marker| line.starts_with(marker))
+}
+
+/// Blank-line separated blocks of Markdown lines. A code fence stays one
+/// block even if it contains blank lines.
+fn markdown_blocks<'a, 'b>(lines: &'b [&'a str]) -> Vec<&'b [&'a str]> {
+    let mut blocks = Vec::new();
+    let mut start = None;
+    let mut fence: Option<&str> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if trimmed.is_empty() => {
+                if let Some(start) = start.take() {
+                    blocks.push(&lines[start..i]);
+                }
+                continue;
+            }
+            None => fence = fence_marker(trimmed),
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        blocks.push(&lines[start..]);
+    }
+
+    blocks
+}
+
+/// Code fences and tables are split between lines rather than sentences
+fn is_verbatim_block(block: &str) -> bool {
+    fence_marker(block.trim_start()).is_some() || block.lines().all(|l| l.trim_start().starts_with('|'))
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Lines that only close a block (`}`, `);`, `end`) belong to the
+/// definition above them
+fn is_closing_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with(['}', ')', ']']) || trimmed == "end" || trimmed == "fi" || trimmed == "done"
+}
+
+/// Indentation of the outermost definitions among `lines`
+fn code_level(lines: &[&str]) -> Option<usize> {
+    let content = lines.iter().filter(|l| !l.trim().is_empty());
+    content.clone()
+        .filter(|l| !is_closing_line(l))
+        .map(|l| indentation(l))
+        .min()
+        .or_else(|| content.map(|l| indentation(l)).min())
+}
+
+/// Split lines into definitions at indentation `level`. A definition starts
+/// at a line at that level that follows a blank line, a closing line or a
+/// more deeply indented line, so leading comments, attributes and
+/// decorators stay with the definition they annotate.
+fn code_units<'a>(lines: &'a [&'a str], level: usize) -> Vec<&'a [&'a str]> {
+    let mut units = Vec::new();
+    let mut start = 0;
+
+    for i in 1..lines.len() {
+        let (previous, line) = (lines[i - 1], lines[i]);
+        let starts_unit = !line.trim().is_empty()
+            && indentation(line) == level
+            && !is_closing_line(line)
+            && (previous.trim().is_empty()
+                || indentation(previous) > level
+                || (indentation(previous) == level && is_closing_line(previous)));
+        if starts_unit {
+            units.push(&lines[start..i]);
+            start = i;
+        }
+    }
+    units.push(&lines[start..]);
+
+    units.retain(|u| u.iter().any(|l| !l.trim().is_empty()));
+    units
+}
+
+/// First line of a definition that isn't a comment, attribute or
+/// decorator, without its opening brace or colon
+fn code_signature(unit: &[&str]) -> Option<String> {
+    const SKIPPED_PREFIXES: [&str; 8] = ["//", "#", "/*", "*", "@", "--", "\"\"\"", "'''"];
+
+    let line = unit.iter()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && !SKIPPED_PREFIXES.iter().any(|prefix| l.starts_with(prefix)))?;
+    let signature = line.trim_end_matches(['{', ':', ' ']);
+    if signature.is_empty() {
+        return None;
+    }
+
+    Some(signature.chars().take(MAX_SIGNATURE_CHARS).collect())
+}
+
+/// CSV records and their source spans, keeping quoted fields that contain
+/// line breaks together
+fn csv_records(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut records = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_quotes = false;
+
+    for line in text.lines() {
+        current.push(line);
+        in_quotes ^= line.matches('"').count() % 2 == 1;
+        if !in_quotes {
+            if current.iter().any(|l| !l.trim().is_empty()) {
+                records.push(join_lines(text, &current));
+            }
+            current.clear();
+        }
+    }
+    if current.iter().any(|l| !l.trim().is_empty()) {
+        records.push(join_lines(text, &current));
+    }
+
+    records
+}
+
+/// Byte range of `part` in `source`, which it must be a slice of
+fn span_of(source: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - source.as_ptr() as usize;
+    debug_assert!(start + part.len() <= source.len(), "not a slice of the source");
+    start..start + part.len()
+}
+
+/// Lines (slices of `source`) joined back into one text, without trailing
+/// whitespace, and the source span from the first line to the last
+fn join_lines(source: &str, lines: &[&str]) -> (String, Range<usize>) {
+    let text = lines.join("\n").trim_end().to_string();
+    let span = match (lines.first(), lines.last()) {
+        (Some(first), Some(last)) => span_of(source, first).start..span_of(source, last).end,
+        _ => 0..0,
+    };
+    (text, span)
+}
+
+/// Source span of `range` within `text`, a piece taken from `span` of the
+/// source. Exact if the piece is a verbatim copy of the source, otherwise
+/// the whole piece's span.
+fn sub_span(text: &str, span: &Range<usize>, range: Range<usize>) -> Range<usize> {
+    if text.len() == span.len() {
+        span.start + range.start..span.start + range.end
+    } else {
+        span.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn breadcrumbs(chunks: &[TextChunk]) -> Vec<String> {
+        chunks.iter().map(|c| c.headings.join(" > ")).collect()
+    }
+
+    #[test]
+    fn test_markdown_sections_keep_breadcrumbs_and_blocks() {
+        let text = "# Guide\nIntro.\n\n## Install\n\n| OS | Command |\n|----|---------|\n| mac | brew install x |\n\n\
+                    ```sh\n# not a heading\n\nx --init\n```\n\n## Usage\nRun it.\n### Flags\nUse -v.\n# Appendix\nMore.";
+        let chunks = DocumentPipeline::new().chunk_document(text, DocumentFormat::Markdown, 512, 50);
+
+        assert_eq!(breadcrumbs(&chunks), vec!["Guide", "Guide > Install", "Guide > Usage", "Guide > Usage > Flags", "Appendix"]);
+        assert!(chunks[1].text.contains("| OS | Command |\n|----|---------|\n| mac | brew install x |"));
+        assert!(chunks[1].text.contains("```sh\n# not a heading\n\nx --init\n```"));
+    }
+
+    #[test]
+    fn test_rst_sections() {
+        let text = "=====\nTitle\n=====\n\nIntro.\n\nSetup\n-----\nSteps.\n\nDetails\n~~~~~~~\nMore.\n\nUsage\n-----\nRun.";
+        let chunks = DocumentPipeline::new().chunk_document(text, DocumentFormat::Rst, 512, 50);
+
+        assert_eq!(breadcrumbs(&chunks), vec!["Title", "Title > Setup", "Title > Setup > Details", "Title > Usage"]);
+        assert_eq!(chunks[1].text, "Setup\nSteps.");
+    }
+
+    #[test]
+    fn test_code_split_at_definitions() {
+        let body: String = (0..20).map(|i| format!("        let value_{} = self.compute({});\n", i, i)).collect();
+        let text = format!(
+            "use std::fmt;\n\n/// A widget\n#[derive(Debug)]\npub struct Widget {{\n    size: usize,\n}}\n\n\
+             impl Widget {{\n    pub fn grow(&mut self) {{\n{body}    }}\n\n    pub fn shrink(&mut self) {{\n{body}    }}\n}}\n"
+        );
+        let chunks = DocumentPipeline::new().chunk_document(&text, DocumentFormat::Code, 300, 0);
+
+        assert_eq!(breadcrumbs(&chunks), vec!["", "impl Widget > pub fn grow(&mut self)", "impl Widget > pub fn shrink(&mut self)"]);
+        assert!(chunks[0].text.starts_with("use std::fmt;") && chunks[0].text.ends_with("size: usize,\n}"));
+        assert!(chunks[1].text.starts_with("impl Widget {\n    pub fn grow(&mut self) {\n"));
+        assert!(chunks[2].text.starts_with("    pub fn shrink(&mut self) {\n") && chunks[2].text.ends_with("    }\n}"));
+    }
+
+    #[test]
+    fn test_csv_row_groups_repeat_header() {
+        let mut text = String::from("id,name,notes\n");
+        for i in 0..50 {
+            text.push_str(&format!("{},item {},\"line one\nline two\"\n", i, i));
+        }
+        let chunks = DocumentPipeline::new().chunk_document(&text, DocumentFormat::Csv, 100, 0);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.text.starts_with("id,name,notes\n"));
+            assert!(chunk.token_count <= 100);
+            assert_eq!(chunk.text.matches('"').count() % 2, 0, "quoted fields are never split");
+        }
+        assert_eq!(chunks.iter().map(|c| c.text.matches("item ").count()).sum::<usize>(), 50);
+    }
+
+    #[test]
+    fn test_chunk_ids_are_deterministic() {
+        let id = chunk_id("default", "doc", 3, "Some text.");
+        assert_eq!(id, chunk_id("default", "doc", 3, "Some text."));
+        assert_eq!(id.len(), 36);
+
+        assert_ne!(id, chunk_id("default", "doc", 3, "Some text!"));
+        assert_ne!(id, chunk_id("default", "doc", 4, "Some text."));
+        assert_ne!(id, chunk_id("default", "other", 3, "Some text."));
+        assert_ne!(id, chunk_id("work", "doc", 3, "Some text."));
+    }
+
+    #[test]
+    fn test_chunks_record_source_locations() {
+        let pipeline = DocumentPipeline::new();
+        let text = "# Intro\nÜber alles.\n\n# Details\nSee the table.\n";
+        let chunks = pipeline.chunk_document(text, DocumentFormat::Markdown, 512, 0);
+        assert_eq!(&text[chunks[1].span.clone()], "# Details\nSee the table.");
+
+        let page_starts = [0, text.find("# Details").unwrap()];
+        let source_map = SourceMap::new(text, &page_starts);
+        assert_eq!(
+            source_map.locate(&chunks[0].span),
+            ChunkLocation { char_start: 0, char_end: 19, line_start: 1, line_end: 2, page_start: Some(1), page_end: Some(1) }
+        );
+        assert_eq!(
+            source_map.locate(&chunks[1].span),
+            ChunkLocation { char_start: 21, char_end: 45, line_start: 4, line_end: 5, page_start: Some(2), page_end: Some(2) }
+        );
+
+        // The repeated header row is not part of a CSV chunk's span
+        let csv = "id,name\n1,a\n2,b\n";
+        let chunks = pipeline.chunk_document(csv, DocumentFormat::Csv, 512, 0);
+        assert_eq!(&csv[chunks[0].span.clone()], "1,a\n2,b");
+    }
+
+    fn sentences(text: &str) -> Vec<&str> {
+        DocumentPipeline::new().split_sentences(text).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn test_sentences_english() {
+        assert_eq!(
+            sentences("Dr. Smith measured 3.14 units at 5 p.m. today. He used v1.2.0, e.g. the latest release! Did it work?\nYes"),
+            vec![
+                "Dr. Smith measured 3.14 units at 5 p.m. today.",
+                "He used v1.2.0, e.g. the latest release!",
+                "Did it work?",
+                "Yes",
+            ]
+        );
+        assert_eq!(sentences("Written by J. R. R. Tolkien. Published in 1954."), vec!["Written by J. R. R. Tolkien.", "Published in 1954."]);
+        assert_eq!(sentences("President Harry S. Truman spoke. See no. 5 here."), vec!["President Harry S. Truman spoke.", "See no. 5 here."]);
+    }
+
+    #[test]
+    fn test_sentences_end_at_ambiguous_periods() {
+        for (text, first) in [
+            ("We chose plan B. Then we shipped.", "We chose plan B."),
+            ("Take vitamin C. It helps.", "Take vitamin C."),
+            ("The answer is no. We tried.", "The answer is no."),
+            ("Founded by Acme Co. The rest is history.", "Founded by Acme Co."),
+            ("Es liegt in der Hauptstr. Dann links.", "Es liegt in der Hauptstr."),
+        ] {
+            assert_eq!(sentences(text)[0], first, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_sentences_german() {
+        assert_eq!(
+            sentences("Das ist z. B. ein Test. Herr Dr. Müller kommt um 10.30 Uhr, d.h. pünktlich. Bzw. früher!"),
+            vec!["Das ist z. B. ein Test.", "Herr Dr. Müller kommt um 10.30 Uhr, d.h. pünktlich.", "Bzw. früher!"]
+        );
+    }
+
+    #[test]
+    fn test_sentences_chinese() {
+        assert_eq!(
+            sentences("今天天气很好。我们去公园吧！你觉得呢？好的"),
+            vec!["今天天气很好。", "我们去公园吧！", "你觉得呢？", "好的"]
+        );
+    }
+
+    #[test]
+    fn test_overlap_is_measured_in_tokens() {
+        // 40-byte (10-token) sentences
+        let text: Vec<String> = (0..10).map(|i| format!("S{:02} {}.", i, "x".repeat(35))).collect();
+        let chunks = DocumentPipeline::new().chunk_text(&text.join(" "), 50, 25);
+
+        assert!(chunks[0].text.starts_with("S00 ") && chunks[0].text.ends_with(&text[3]));
+        assert!(chunks[1].text.starts_with("S02 "), "two sentences (21 tokens) of overlap");
+        assert!(chunks.iter().all(|c| c.token_count <= 50));
+    }
+
+    #[test]
+    fn test_paragraph_and_recursive_strategies() {
+        let pipeline = DocumentPipeline::new();
+        let first = "First paragraph. It has two sentences.";
+        let second = "Second paragraph\nspans two lines.";
+        let text = format!("{}\n  \n{}\n\n", first, second);
+
+        let paragraphs = pipeline.chunk(&text, ChunkingStrategy::Paragraph, DocumentFormat::Plain, 12, 0);
+        assert_eq!(paragraphs.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), vec![first, second]);
+
+        // Too small for the paragraph, so it is split at the line break first
+        let recursive = pipeline.chunk(&text, ChunkingStrategy::RecursiveCharacter, DocumentFormat::Plain, 5, 0);
+        assert_eq!(recursive.last().unwrap().text, "spans two lines.");
+
+        assert_eq!(ChunkingStrategy::parse(" Recursive_Character"), Some(ChunkingStrategy::RecursiveCharacter));
+        assert_eq!(ChunkingStrategy::parse("tokens"), None);
+    }
+
+    /// Words, sentence ends, line breaks, non-ASCII text and words longer
+    /// than a whole chunk
+    fn document_text() -> impl Strategy<Value = String> {
+        proptest::collection::vec(
+            prop_oneof![
+                6 => "[a-zA-Z]{1,12} ",
+                2 => "[a-z]{1,12}[.!?] ",
+                1 => "\n\n?",
+                1 => "[äöü漢字🙂]{1,6} ?",
+                1 => "[a-z]{40,400}",
+            ],
+            0..200,
+        )
+        .prop_map(|parts| parts.concat())
+    }
+
+    /// CSV whose header row may be longer than a whole chunk
+    fn csv_text() -> impl Strategy<Value = String> {
+        (
+            proptest::collection::vec("[a-z]{1,60}", 1..12),
+            proptest::collection::vec(proptest::collection::vec("[a-z0-9 ]{0,30}", 1..8), 0..30),
+        )
+            .prop_map(|(header, rows)| {
+                std::iter::once(header).chain(rows).map(|row| row.join(",")).collect::<Vec<_>>().join("\n")
+            })
+    }
+
+    /// Code with nested definitions under long doc comments
+    fn code_text() -> impl Strategy<Value = String> {
+        let definition = (
+            proptest::collection::vec("[a-z ]{0,80}", 0..8),
+            "[a-z_]{1,60}",
+            proptest::collection::vec(("[a-z0-9 =;]{1,60}", any::<bool>()), 1..10),
+        );
+        proptest::collection::vec(definition, 1..6).prop_map(|definitions| {
+            let mut lines = Vec::new();
+            for (doc, name, body) in definitions {
+                lines.extend(doc.iter().map(|line| format!("/// {}", line)));
+                lines.push(format!("fn {}() {{", name));
+                for (statement, nested) in body {
+                    if nested {
+                        lines.push(format!("    /// {}", statement));
+                        lines.push(format!("    fn {}() {{", name));
+                        lines.push(format!("        {}", statement));
+                        lines.push("    }".to_string());
+                    } else {
+                        lines.push(format!("    {}", statement));
+                    }
+                }
+                lines.push("}".to_string());
+            }
+            lines.join("\n")
+        })
+    }
+
+    fn packing_strategy() -> impl Strategy<Value = ChunkingStrategy> {
+        prop_oneof![
+            Just(ChunkingStrategy::FixedSize),
+            Just(ChunkingStrategy::Sentence),
+            Just(ChunkingStrategy::Paragraph),
+            Just(ChunkingStrategy::RecursiveCharacter),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_chunks_fit_the_limit(
+            text in document_text(),
+            strategy in packing_strategy(),
+            chunk_size in 1usize..200,
+            overlap in 0usize..100,
+        ) {
+            let pipeline = DocumentPipeline::new();
+            for chunk in pipeline.chunk(&text, strategy, DocumentFormat::Plain, chunk_size, overlap) {
+                prop_assert!(chunk.token_count <= chunk_size, "{} tokens > {}", chunk.token_count, chunk_size);
+                prop_assert_eq!(chunk.token_count, pipeline.estimate_tokens(&chunk.text));
+                prop_assert_eq!(&text[chunk.span.clone()], chunk.text.as_str());
+            }
+        }
+
+        #[test]
+        fn prop_structured_chunks_fit_the_limit(
+            (text, format) in prop_oneof![
+                csv_text().prop_map(|text| (text, DocumentFormat::Csv)),
+                code_text().prop_map(|text| (text, DocumentFormat::Code)),
+            ],
+            chunk_size in 1usize..200,
+        ) {
+            let pipeline = DocumentPipeline::new();
+            for chunk in pipeline.chunk(&text, ChunkingStrategy::SemanticBoundary, format, chunk_size, 0) {
+                prop_assert!(chunk.token_count <= chunk_size, "{} tokens > {}", chunk.token_count, chunk_size);
+                prop_assert_eq!(chunk.token_count, pipeline.estimate_tokens(&chunk.text));
+            }
+        }
+
+        #[test]
+        fn prop_chunks_cover_text(
+            text in document_text(),
+            strategy in packing_strategy(),
+            chunk_size in 1usize..200,
+            overlap in 0usize..100,
+        ) {
+            let ranges = DocumentPipeline::new().chunk_ranges(&text, strategy, chunk_size, overlap);
+            let mut covered = 0;
+            let mut previous: Option<Range<usize>> = None;
+
+            for range in ranges {
+                if let Some(previous) = previous {
+                    prop_assert!(range.start > previous.start, "chunks advance");
+                    if overlap == 0 {
+                        prop_assert!(range.start >= previous.end, "no overlap requested");
+                    }
+                }
+                if range.start > covered {
+                    prop_assert!(text[covered..range.start].trim().is_empty(), "text skipped before {:?}", range);
+                }
+                covered = covered.max(range.end);
+                previous = Some(range);
+            }
+            prop_assert!(text[covered..].trim().is_empty(), "text skipped at the end");
+        }
+    }
+}
@@ -28,6 +28,14 @@ struct GeminiEmbedding {
     values: Vec<f32>,
 }
 
+#[derive(Debug, Deserialize)]
+struct GeminiBatchEmbedResponse {
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+/// Texts sent per request by `embed_batch`; Gemini accepts at most 100
+const BATCH_SIZE: usize = 100;
+
 impl EmbeddingService {
     pub fn new(provider: &str, api_key: &str, model: Option<&str>) -> Self {
         Self::with_base_url(provider, api_key, model, None)
@@ -71,13 +79,31 @@ impl EmbeddingService {
         }
     }
 
-    #[allow(dead_code)]
+    /// Embed several texts, up to `BATCH_SIZE` per request. Ollama has no
+    /// batch endpoint on every version, so its texts are embedded one by one.
     pub async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         let mut embeddings = Vec::with_capacity(texts.len());
 
-        for text in texts {
-            let embedding = self.embed(text).await?;
-            embeddings.push(embedding);
+        for batch in texts.chunks(BATCH_SIZE) {
+            let batch_embeddings = match self.provider.as_str() {
+                "gemini" => self.embed_batch_gemini(batch).await?,
+                "openai" => self.embed_batch_openai(batch).await?,
+                _ => {
+                    let mut batch_embeddings = Vec::with_capacity(batch.len());
+                    for text in batch {
+                        batch_embeddings.push(self.embed(text).await?);
+                    }
+                    batch_embeddings
+                }
+            };
+            if batch_embeddings.len() != batch.len() {
+                return Err(AppError::Api(format!(
+                    "Expected {} embeddings but the provider returned {}",
+                    batch.len(),
+                    batch_embeddings.len()
+                )));
+            }
+            embeddings.extend(batch_embeddings);
         }
 
         Ok(embeddings)
@@ -115,6 +141,44 @@ impl EmbeddingService {
         Ok(result.embedding.values)
     }
 
+    async fn embed_batch_gemini(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:batchEmbedContents?key={}",
+            self.model, self.api_key
+        );
+
+        let requests: Vec<serde_json::Value> = texts
+            .iter()
+            .map(|text| {
+                serde_json::json!({
+                    "model": format!("models/{}", self.model),
+                    "content": {
+                        "parts": [{"text": text}]
+                    }
+                })
+            })
+            .collect();
+        let body = serde_json::json!({ "requests": requests });
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AppError::Api(redact(
+                &self.api_key,
+                format!("Gemini embedding error: {}", response.status()),
+            )));
+        }
+
+        let result: GeminiBatchEmbedResponse = response.json().await?;
+        Ok(result.embeddings.into_iter().map(|e| e.values).collect())
+    }
+
     async fn embed_openai(&self, text: &str) -> Result<Vec<f32>> {
         let url = "https://api.openai.com/v1/embeddings";
 
@@ -152,6 +216,46 @@ impl EmbeddingService {
         Ok(result.data.first().map(|e| e.embedding.clone()).unwrap_or_default())
     }
 
+    async fn embed_batch_openai(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = "https://api.openai.com/v1/embeddings";
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "input": texts,
+        });
+
+        let response = self
+            .client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AppError::Api(redact(
+                &self.api_key,
+                format!("OpenAI embedding error: {}", response.status()),
+            )));
+        }
+
+        #[derive(Deserialize)]
+        struct OpenAIResponse {
+            data: Vec<OpenAIEmbedding>,
+        }
+        #[derive(Deserialize)]
+        struct OpenAIEmbedding {
+            index: usize,
+            embedding: Vec<f32>,
+        }
+
+        // The response lists embeddings with the index of their input
+        let mut result: OpenAIResponse = response.json().await?;
+        result.data.sort_by_key(|e| e.index);
+        Ok(result.data.into_iter().map(|e| e.embedding).collect())
+    }
+
     async fn embed_ollama(&self, text: &str) -> Result<Vec<f32>> {
         let base = self.base_url.as_deref().unwrap_or("http://localhost:11434");
         let url = format!("{}/api/embeddings", base.trim_end_matches('/'));
@@ -185,6 +289,11 @@ impl EmbeddingService {
         Ok(result.embedding)
     }
 
+    /// Name of the embedding model in use
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
     #[allow(dead_code)]
     pub fn dimension(&self) -> usize {
         match (self.provider.as_str(), self.model.as_str()) {
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::path::Path;
+use quick_xml::events::{BytesText, Event};
+use quick_xml::Reader;
+use crate::error::{AppError, Result};
+use crate::services::office_documents::{attribute, open_archive, read_part, resolve_target, xml_error};
+
+/// One content document of an EPUB book
+pub struct Chapter {
+    /// Title from the table of contents, or else the chapter's first heading
+    pub title: Option<String>,
+    pub xhtml: String,
+}
+
+/// The package document's view of the book
+struct Package {
+    /// Archive paths of the content documents, in reading order
+    spine: Vec<String>,
+    /// EPUB 3 navigation document
+    navigation: Option<String>,
+    /// EPUB 2 table of contents
+    ncx: Option<String>,
+}
+
+/// The chapters of an EPUB book in reading order. Content documents the
+/// spine marks as non-linear (pop-up notes, answer keys) are left out.
+pub fn chapters(path: &Path) -> Result<Vec<Chapter>> {
+    let mut archive = open_archive(path)?;
+    let container = read_part(&mut archive, "META-INF/container.xml")?
+        .ok_or_else(|| AppError::File("Not an EPUB file: META-INF/container.xml is missing".to_string()))?;
+    let package_path = package_path(&container)?
+        .ok_or_else(|| AppError::File("Not an EPUB file: no package document is listed".to_string()))?;
+    let package_xml = read_part(&mut archive, &package_path)?
+        .ok_or_else(|| AppError::File(format!("Not an EPUB file: {} is missing", package_path)))?;
+    let package = package(&package_xml, directory(&package_path))?;
+
+    // Titles are a nicety, so a table of contents that doesn't parse is
+    // ignored rather than failing the book
+    let mut titles = HashMap::new();
+    if let Some(nav) = &package.navigation {
+        if let Some(xml) = read_part(&mut archive, nav)? {
+            titles = navigation_titles(&xml, directory(nav)).unwrap_or_default();
+        }
+    }
+    if titles.is_empty() {
+        if let Some(ncx) = &package.ncx {
+            if let Some(xml) = read_part(&mut archive, ncx)? {
+                titles = ncx_titles(&xml, directory(ncx)).unwrap_or_default();
+            }
+        }
+    }
+
+    let mut chapters = Vec::with_capacity(package.spine.len());
+    for path in &package.spine {
+        let Some(xhtml) = read_part(&mut archive, path)? else {
+            continue;
+        };
+        let title = titles.get(path).cloned().or_else(|| first_heading(&xhtml));
+        chapters.push(Chapter { title, xhtml });
+    }
+
+    Ok(chapters)
+}
+
+/// Archive path of the package document, from `META-INF/container.xml`
+fn package_path(container: &str) -> Result<Option<String>> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attribute(&e, b"full-path") {
+                    return Ok(Some(path));
+                }
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Read the manifest and spine of the package document in `directory`
+fn package(xml: &str, directory: &str) -> Result<Package> {
+    let mut reader = Reader::from_str(xml);
+    // Manifest items by ID, as (path, properties)
+    let mut manifest: HashMap<String, (String, String)> = HashMap::new();
+    let mut spine_ids = Vec::new();
+    let mut ncx_id = None;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attribute(&e, b"id"), attribute(&e, b"href")) {
+                        let properties = attribute(&e, b"properties").unwrap_or_default();
+                        manifest.insert(id, (resolve_href(directory, &href), properties));
+                    }
+                }
+                b"spine" => ncx_id = attribute(&e, b"toc"),
+                b"itemref" if attribute(&e, b"linear").as_deref() != Some("no") => {
+                    spine_ids.extend(attribute(&e, b"idref"));
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(Package {
+        spine: spine_ids.iter().filter_map(|id| manifest.get(id)).map(|(path, _)| path.clone()).collect(),
+        navigation: manifest
+            .values()
+            .find(|(_, properties)| properties.split_whitespace().any(|p| p == "nav"))
+            .map(|(path, _)| path.clone()),
+        ncx: ncx_id.and_then(|id| manifest.get(&id)).map(|(path, _)| path.clone()),
+    })
+}
+
+/// Titles of the table of contents entries in an EPUB 3 navigation
+/// document, by the archive path they link to. A document linked from
+/// several entries takes the first one's title.
+fn navigation_titles(xml: &str, directory: &str) -> Result<HashMap<String, String>> {
+    let mut reader = Reader::from_str(xml);
+    reader.check_end_names(false);
+    let mut titles = HashMap::new();
+    let mut in_toc = false;
+    // The open link's target and text
+    let mut link: Option<(String, String)> = None;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"nav" => in_toc = attribute(&e, b"epub:type").is_some_and(|t| t.split_whitespace().any(|t| t == "toc")),
+                b"a" if in_toc => link = attribute(&e, b"href").map(|href| (href, String::new())),
+                _ => {}
+            },
+            Event::Text(t) => {
+                if let Some((_, title)) = &mut link {
+                    title.push_str(&text(&t));
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"nav" => in_toc = false,
+                b"a" => {
+                    if let Some((href, title)) = link.take() {
+                        insert_title(&mut titles, resolve_href(directory, &href), &title);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(titles)
+}
+
+/// Titles of the navigation points in an EPUB 2 NCX file, by the archive
+/// path they point to
+fn ncx_titles(xml: &str, directory: &str) -> Result<HashMap<String, String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut titles = HashMap::new();
+    // The label of the current navigation point, which precedes its target
+    let mut label: Option<String> = None;
+    let mut in_label = false;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) if e.local_name().as_ref() == b"text" => {
+                in_label = true;
+                label = Some(String::new());
+            }
+            Event::Text(t) if in_label => {
+                if let Some(label) = &mut label {
+                    label.push_str(&text(&t));
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"text" => in_label = false,
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"content" => {
+                if let (Some(src), Some(label)) = (attribute(&e, b"src"), label.take()) {
+                    insert_title(&mut titles, resolve_href(directory, &src), &label);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(titles)
+}
+
+fn insert_title(titles: &mut HashMap<String, String>, path: String, title: &str) {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !title.is_empty() {
+        titles.entry(path).or_insert(title);
+    }
+}
+
+/// Text of the first `h1`–`h3` heading of a content document, if it has
+/// one and parses
+fn first_heading(xhtml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xhtml);
+    reader.check_end_names(false);
+    let mut heading: Option<String> = None;
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) if matches!(e.local_name().as_ref(), b"h1" | b"h2" | b"h3") => heading = Some(String::new()),
+            Event::Text(t) => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(&text(&t));
+                }
+            }
+            Event::End(e) if matches!(e.local_name().as_ref(), b"h1" | b"h2" | b"h3") => {
+                let title = heading.take()?.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !title.is_empty() {
+                    return Some(title);
+                }
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Unescaped text, or the raw text if it uses entities XML doesn't define
+/// (XHTML content often has `&nbsp;`)
+fn text(t: &BytesText) -> String {
+    t.unescape()
+        .map(|text| text.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(t).into_owned())
+}
+
+/// Archive path of a (URL-encoded) href relative to `directory`, without
+/// its fragment
+fn resolve_href(directory: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| href.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    resolve_target(directory, &String::from_utf8_lossy(&decoded))
+}
+
+fn directory(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(directory, _)| directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_chapters_follow_the_spine_with_toc_titles() {
+        let container = r#"<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles>
+            <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+        </rootfiles></container>"#;
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf"><manifest>
+            <item id="nav" href="nav.xhtml" properties="nav" media-type="application/xhtml+xml"/>
+            <item id="intro" href="text/intro.xhtml" media-type="application/xhtml+xml"/>
+            <item id="ch1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+            <item id="answers" href="text/answers.xhtml" media-type="application/xhtml+xml"/>
+        </manifest><spine>
+            <itemref idref="ch1"/><itemref idref="answers" linear="no"/><itemref idref="intro"/>
+        </spine></package>"#;
+        let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+            <nav epub:type="toc"><ol>
+                <li><a href="text/chapter%201.xhtml#start">Ownership &amp; Borrowing</a></li>
+                <li><a href="text/chapter%201.xhtml#rules">The Rules</a></li>
+            </ol></nav>
+            <nav epub:type="landmarks"><ol><li><a href="text/intro.xhtml">Start here</a></li></ol></nav>
+        </body></html>"#;
+        let chapter = r#"<html><body><h1>Chapter 1</h1><p>Every value has an owner.</p></body></html>"#;
+        let intro = r#"<html><body><section><h2>Why <em>Rust</em>?</h2><p>Safety&nbsp;first.</p></section></body></html>"#;
+
+        let path = std::env::temp_dir().join(format!("omnirecall-{}-test.epub", uuid::Uuid::new_v4()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in [
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", package),
+            ("OEBPS/nav.xhtml", nav),
+            ("OEBPS/text/chapter 1.xhtml", chapter),
+            ("OEBPS/text/intro.xhtml", intro),
+            ("OEBPS/text/answers.xhtml", "<html><body><p>42</p></body></html>"),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let chapters = chapters(&path).unwrap();
+        let titles: Vec<Option<&str>> = chapters.iter().map(|c| c.title.as_deref()).collect();
+        assert_eq!(titles, vec![Some("Ownership & Borrowing"), Some("Why Rust?")]);
+        assert_eq!(chapters[0].xhtml, chapter);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,290 @@
+use scraper::{ElementRef, Html, Node};
+use crate::services::markdown::{ListMarker, MarkdownWriter};
+
+/// Elements whose content is never page text
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "iframe", "svg", "canvas", "object",
+    "button", "select", "textarea", "nav", "aside",
+];
+
+/// ARIA roles of navigation, banners and other page furniture
+const SKIPPED_ROLES: &[&str] = &["navigation", "banner", "contentinfo", "complementary", "search"];
+
+/// Elements that start a new block of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "html", "body", "main", "article", "section", "header", "footer", "div", "p", "blockquote",
+    "figure", "figcaption", "address", "details", "summary", "dl", "dt", "dd", "hr", "form",
+    "fieldset", "center", "caption",
+];
+
+/// Convert an HTML page to Markdown: headings, paragraphs, lists, tables,
+/// code blocks and links are kept, while navigation, sidebars, the page
+/// header and footer, and scripts are left out. If the page marks its main
+/// content, only that is converted. Malformed markup is repaired the way a
+/// browser would.
+pub fn html_to_markdown(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let root = document.root_element();
+    let main = root
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|e| e.value().name() == "main" || e.attr("role") == Some("main"));
+
+    let mut converter = Converter::default();
+    converter.element(main.unwrap_or(root));
+    converter.end_block(None);
+    converter.markdown.finish()
+}
+
+#[derive(Default)]
+struct Converter {
+    markdown: MarkdownWriter,
+    /// Inline text of the block being read
+    inline: String,
+    /// Enclosing lists, innermost last
+    lists: Vec<List>,
+    /// Open tables, as rows of cells
+    tables: Vec<Vec<Vec<String>>>,
+}
+
+struct List {
+    /// Number of the next item, for ordered lists
+    next_number: Option<usize>,
+    /// Marker of the current item, until its first block is written
+    marker: Option<ListMarker>,
+}
+
+impl Converter {
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            if let Some(child) = ElementRef::wrap(child) {
+                self.element(child);
+            } else if let Node::Text(text) = child.value() {
+                self.inline.push_str(text);
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if is_boilerplate(element) {
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.end_block(None);
+                self.children(element);
+                self.end_block(name[1..].parse().ok());
+            }
+            "ul" | "ol" | "menu" => {
+                self.end_block(None);
+                let start = element.attr("start").and_then(|start| start.trim().parse().ok());
+                self.lists.push(List {
+                    next_number: (name == "ol").then(|| start.unwrap_or(1)),
+                    marker: None,
+                });
+                self.children(element);
+                self.end_block(None);
+                self.lists.pop();
+            }
+            "li" => {
+                self.end_block(None);
+                if let Some(list) = self.lists.last_mut() {
+                    list.marker = Some(match list.next_number {
+                        Some(next) => {
+                            // A `value` renumbers the item and those after it
+                            let number = element.attr("value").and_then(|value| value.trim().parse().ok()).unwrap_or(next);
+                            list.next_number = Some(number + 1);
+                            ListMarker::Number(number)
+                        }
+                        None => ListMarker::Bullet,
+                    });
+                }
+                self.children(element);
+                self.end_block(None);
+            }
+            "pre" => {
+                self.end_block(None);
+                let code: String = element.text().collect();
+                let language = element
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .chain([element])
+                    .filter_map(|e| e.attr("class"))
+                    .flat_map(str::split_whitespace)
+                    .find_map(|class| class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")));
+                match self.cell() {
+                    Some(cell) => append_text(cell, &code),
+                    None => self.markdown.code_block(language, &code),
+                }
+            }
+            "table" => {
+                self.end_block(None);
+                self.tables.push(Vec::new());
+                self.children(element);
+                self.end_block(None);
+                if let Some(rows) = self.tables.pop() {
+                    match self.cell() {
+                        Some(cell) => {
+                            for row in rows {
+                                append_text(cell, &row.join(" "));
+                            }
+                        }
+                        None => self.markdown.table(&rows),
+                    }
+                }
+            }
+            "tr" => {
+                self.end_block(None);
+                if let Some(table) = self.tables.last_mut() {
+                    table.push(Vec::new());
+                }
+                self.children(element);
+            }
+            "td" | "th" => {
+                self.end_block(None);
+                if let Some(row) = self.tables.last_mut().and_then(|table| table.last_mut()) {
+                    row.push(String::new());
+                }
+                self.children(element);
+                self.end_block(None);
+            }
+            "a" => {
+                let start = self.inline.len();
+                self.children(element);
+                let href = element.attr("href").map(str::trim).filter(|href| {
+                    !href.is_empty() && !href.starts_with('#') && !href.to_ascii_lowercase().starts_with("javascript:")
+                });
+                let content = &self.inline[start..];
+                let text = collapse_whitespace(content);
+                if let (Some(href), false) = (href, text.is_empty()) {
+                    // Keep the spacing around the link text outside the link
+                    let before = if content.starts_with(char::is_whitespace) { " " } else { "" };
+                    let after = if content.ends_with(char::is_whitespace) { " " } else { "" };
+                    let link = format!("{}[{}]({}){}", before, text, href.replace(' ', "%20"), after);
+                    self.inline.truncate(start);
+                    self.inline.push_str(&link);
+                }
+            }
+            "br" => self.inline.push('\n'),
+            "img" => {
+                if let Some(alt) = element.attr("alt").filter(|alt| !alt.trim().is_empty()) {
+                    self.inline.push_str(&format!(" {} ", alt));
+                }
+            }
+            name if BLOCK_ELEMENTS.contains(&name) => {
+                self.end_block(None);
+                self.children(element);
+                self.end_block(None);
+            }
+            _ => self.children(element),
+        }
+    }
+
+    /// Write out the inline text read so far: into the open table cell if
+    /// there is one, else as a heading of `heading` level, a list item (or
+    /// a further paragraph of one) or a paragraph
+    fn end_block(&mut self, heading: Option<usize>) {
+        let text = collapse_whitespace(&std::mem::take(&mut self.inline));
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(cell) = self.cell() {
+            append_text(cell, &text);
+        } else if let Some(level) = heading {
+            self.markdown.heading(level, &text);
+        } else if !self.lists.is_empty() {
+            let depth = self.lists.len() - 1;
+            match self.lists[depth].marker.take() {
+                Some(marker) => self.markdown.list_item(depth, marker, &text),
+                None => self.markdown.list_continuation(depth, &text),
+            }
+        } else {
+            self.markdown.paragraph(&text);
+        }
+    }
+
+    /// The innermost open table cell
+    fn cell(&mut self) -> Option<&mut String> {
+        self.tables.last_mut()?.last_mut()?.last_mut()
+    }
+}
+
+/// Whether an element is scripting or page furniture rather than content.
+/// Headers and footers are only furniture outside an article, where they
+/// hold the site's banner and links rather than the article's title.
+fn is_boilerplate(element: ElementRef) -> bool {
+    let name = element.value().name();
+    if SKIPPED_ELEMENTS.contains(&name) {
+        return true;
+    }
+    if element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if element.attr("role").is_some_and(|role| SKIPPED_ROLES.contains(&role)) {
+        return true;
+    }
+
+    matches!(name, "header" | "footer")
+        && !element
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .any(|a| matches!(a.value().name(), "article" | "main" | "section"))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn append_text(target: &mut String, text: &str) {
+    let text = collapse_whitespace(text);
+    if text.is_empty() {
+        return;
+    }
+    if !target.is_empty() {
+        target.push(' ');
+    }
+    target.push_str(&text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converts_structure_and_drops_boilerplate() {
+        let html = r#"<!DOCTYPE html><html><head><title>Guide</title><style>p { color: red }</style></head>
+            <body>
+            <header><a href="/">Home</a> | <a href="/blog">Blog</a></header>
+            <nav><ul><li>Docs</li><li>Pricing</li></ul></nav>
+            <h1>Install &amp; Setup</h1>
+            <p>Run the <a href="https://example.com/get?a=1&amp;b=2">installer</a>,&nbsp;then
+               <b>restart</b>. Stray a < b and </span> tags are fine.
+            <p>Unclosed paragraph <script>track("<p>")</script>ends here.
+            <ol start="3"><li>Download<ul><li>Windows</li><li>macOS</li></ul>Pick one.</li><li>Run</li></ol>
+            <table><tr><th>OS</th><th>Size</th></tr><tr><td>Linux</td><td>12 <i>MB</i></td></tr></table>
+            <pre><code class="language-sh">make install
+make check</code></pre>
+            <div hidden>Cookie settings</div>
+            <footer>&copy; 2024 Example</footer>
+            </body></html>"#;
+
+        assert_eq!(
+            html_to_markdown(html),
+            "# Install & Setup\n\n\
+             Run the [installer](https://example.com/get?a=1&b=2), then restart. Stray a < b and tags are fine.\n\n\
+             Unclosed paragraph ends here.\n\n\
+             3. Download\n   - Windows\n   - macOS\n\n   Pick one.\n\n4. Run\n\n\
+             | OS | Size |\n| --- | --- |\n| Linux | 12 MB |\n\n\
+             ```sh\nmake install\nmake check\n```"
+        );
+    }
+
+    #[test]
+    fn test_prefers_main_content() {
+        let html = "<body><div class=menu>Sign in</div><main><h2>Changelog</h2><p>Fixed a crash.</p></main></body>";
+        assert_eq!(html_to_markdown(html), "## Changelog\n\nFixed a crash.");
+    }
+}
@@ -0,0 +1,443 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::error::{AppError, Result};
+use crate::services::document_pipeline::chunk_id;
+use crate::services::vector_store::{ChunkLocation, DocumentChunk, DocumentRecord, Metadata, VectorStore};
+
+/// Version of the archive layout written by `export_collection`. Bump it
+/// when the layout changes incompatibly; import refuses newer versions.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DOCUMENTS_ENTRY: &str = "documents.json";
+const CHUNKS_ENTRY: &str = "chunks.jsonl";
+
+/// Describes an exported collection. An archive is a zip file holding this
+/// manifest, the document registry entries (`documents.json`) and one JSON
+/// chunk per line, embedding included (`chunks.jsonl`).
+///
+/// Embeddings are always written at full precision, whatever
+/// `embedding_quantization` the exporting store uses, so nothing is lost in
+/// the archive; importing encodes them with the target store's setting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    pub collection_name: String,
+    /// Embedding model the chunks were embedded with
+    pub embedding_model: String,
+    /// Embedding dimensions, if the collection has any chunks
+    pub dimensions: Option<usize>,
+    pub document_count: usize,
+    pub chunk_count: usize,
+    pub exported_at: String,
+}
+
+/// What to do with an archived document whose ID is already indexed in the
+/// target collection
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the existing document
+    #[default]
+    Skip,
+    /// Replace the existing document with the archived one
+    Replace,
+    /// Import the archived document under a new ID
+    KeepBoth,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportSummary {
+    pub collection_id: String,
+    pub documents_imported: usize,
+    pub documents_replaced: usize,
+    pub documents_renamed: usize,
+    pub documents_skipped: usize,
+    pub chunks_imported: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchivedChunk {
+    document_id: String,
+    document_name: String,
+    content: String,
+    embedding: Vec<f32>,
+    chunk_index: i32,
+    token_count: i32,
+    metadata: Metadata,
+    /// Missing from archives written before chunk locations were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<ChunkLocation>,
+}
+
+/// Write a collection's documents, chunks and embeddings to a zip archive
+pub fn export_collection(store: &VectorStore, collection_id: &str, path: &Path) -> Result<ArchiveManifest> {
+    let collection = store.list_collections()?
+        .into_iter()
+        .find(|c| c.id == collection_id)
+        .ok_or_else(|| AppError::Database(format!("Collection not found: {}", collection_id)))?;
+    let documents = store.list_documents(collection_id)?;
+
+    let mut models: Vec<&str> = documents.iter()
+        .map(|d| d.embedding_model.as_str())
+        .filter(|m| !m.is_empty())
+        .collect();
+    models.sort_unstable();
+    models.dedup();
+    if models.len() > 1 {
+        return Err(AppError::Config(format!(
+            "Collection mixes embedding models ({}); re-index it with one model before exporting",
+            models.join(", ")
+        )));
+    }
+
+    let file = File::create(path)?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(CHUNKS_ENTRY, options).map_err(archive_error)?;
+    let mut chunk_count = 0;
+    let mut dimensions = None;
+    for document in &documents {
+        for chunk in store.get_document_chunks(collection_id, &document.id)? {
+            dimensions.get_or_insert(chunk.embedding.len());
+            let line = serde_json::to_string(&ArchivedChunk {
+                document_id: chunk.document_id,
+                document_name: chunk.document_name,
+                content: chunk.content,
+                embedding: chunk.embedding,
+                chunk_index: chunk.chunk_index,
+                token_count: chunk.token_count,
+                metadata: chunk.metadata,
+                location: chunk.location,
+            })?;
+            writeln!(zip, "{}", line)?;
+            chunk_count += 1;
+        }
+    }
+
+    zip.start_file(DOCUMENTS_ENTRY, options).map_err(archive_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&documents)?)?;
+
+    let manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        collection_name: collection.name,
+        embedding_model: models.first().map(|m| m.to_string()).unwrap_or_default(),
+        dimensions,
+        document_count: documents.len(),
+        chunk_count,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+    };
+    zip.start_file(MANIFEST_ENTRY, options).map_err(archive_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+
+    zip.finish().map_err(archive_error)?;
+    Ok(manifest)
+}
+
+/// Read just the manifest of an archive, e.g. to show what it contains
+/// before importing
+pub fn read_manifest(path: &Path) -> Result<ArchiveManifest> {
+    let mut archive = open_archive(path)?;
+    read_manifest_from(&mut archive)
+}
+
+/// Import an archive into `collection_id`, or if that is `None`, into the
+/// collection named in the manifest, which is created if it doesn't exist.
+///
+/// The archive must have been embedded with `embedding_model`, and so must
+/// anything already in the collection, otherwise search would compare
+/// vectors from different spaces. Chunk IDs are derived for the target
+/// collection; documents whose ID already exists in the collection are
+/// handled per `on_conflict`.
+///
+/// The archive is fully read and checked before anything is stored, a new
+/// collection included, and an archive with chunks of documents missing from
+/// `documents.json` is rejected. Each document is then stored in its own transaction, so if a
+/// write fails partway, the documents already imported stay in place and
+/// importing again with `ConflictPolicy::Skip` picks up the rest.
+pub fn import_collection(
+    store: &VectorStore,
+    path: &Path,
+    collection_id: Option<&str>,
+    embedding_model: &str,
+    on_conflict: ConflictPolicy,
+) -> Result<ImportSummary> {
+    let mut archive = open_archive(path)?;
+    let manifest = read_manifest_from(&mut archive)?;
+
+    if !manifest.embedding_model.is_empty() && manifest.embedding_model != embedding_model {
+        return Err(AppError::Config(format!(
+            "Archive was embedded with '{}' but the current embedding model is '{}'",
+            manifest.embedding_model, embedding_model
+        )));
+    }
+
+    let target = match collection_id {
+        Some(collection_id) => {
+            store.ensure_collection(collection_id)?;
+            Some(collection_id.to_string())
+        }
+        None => store.list_collections()?
+            .into_iter()
+            .find(|c| c.name == manifest.collection_name)
+            .map(|c| c.id),
+    };
+    let existing = match &target {
+        Some(collection_id) => store.list_documents(collection_id)?,
+        None => Vec::new(),
+    };
+    if let Some(other) = existing.iter().find(|d| !d.embedding_model.is_empty() && d.embedding_model != embedding_model) {
+        return Err(AppError::Config(format!(
+            "Collection already contains documents embedded with '{}'",
+            other.embedding_model
+        )));
+    }
+
+    let documents: Vec<DocumentRecord> = serde_json::from_reader(
+        archive.by_name(DOCUMENTS_ENTRY).map_err(archive_error)?
+    )?;
+
+    let mut chunks_by_document: std::collections::HashMap<String, Vec<DocumentChunk>> = Default::default();
+    let reader = BufReader::new(archive.by_name(CHUNKS_ENTRY).map_err(archive_error)?);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let chunk: ArchivedChunk = serde_json::from_str(&line)?;
+        if manifest.dimensions.is_some_and(|d| d != chunk.embedding.len()) {
+            return Err(AppError::Config("Archive contains embeddings of inconsistent dimensions".to_string()));
+        }
+        chunks_by_document.entry(chunk.document_id.clone()).or_default().push(DocumentChunk {
+            // Assigned once the document's final ID is known
+            id: String::new(),
+            collection_id: String::new(),
+            document_id: chunk.document_id,
+            document_name: chunk.document_name,
+            content: chunk.content,
+            embedding: chunk.embedding,
+            chunk_index: chunk.chunk_index,
+            token_count: chunk.token_count,
+            metadata: chunk.metadata,
+            location: chunk.location,
+        });
+    }
+
+    let orphans: usize = chunks_by_document
+        .iter()
+        .filter(|(document_id, _)| !documents.iter().any(|d| &d.id == *document_id))
+        .map(|(_, chunks)| chunks.len())
+        .sum();
+    if orphans > 0 {
+        return Err(AppError::File(format!(
+            "Archive is corrupt: {} chunks belong to documents missing from {}",
+            orphans, DOCUMENTS_ENTRY
+        )));
+    }
+
+    let collection_id = match target {
+        Some(collection_id) => collection_id,
+        None => store.create_collection(&manifest.collection_name)?.id,
+    };
+    let collection_id = collection_id.as_str();
+
+    let mut summary = ImportSummary {
+        collection_id: collection_id.to_string(),
+        documents_imported: 0,
+        documents_replaced: 0,
+        documents_renamed: 0,
+        documents_skipped: 0,
+        chunks_imported: 0,
+    };
+
+    for mut document in documents {
+        let mut chunks = chunks_by_document.remove(&document.id).unwrap_or_default();
+
+        if existing.iter().any(|d| d.id == document.id) {
+            match on_conflict {
+                ConflictPolicy::Skip => {
+                    summary.documents_skipped += 1;
+                    continue;
+                }
+                ConflictPolicy::Replace => summary.documents_replaced += 1,
+                ConflictPolicy::KeepBoth => {
+                    document.id = uuid::Uuid::new_v4().to_string();
+                    chunks.iter_mut().for_each(|c| c.document_id = document.id.clone());
+                    summary.documents_renamed += 1;
+                }
+            }
+        }
+
+        document.collection_id = collection_id.to_string();
+        document.embedding_model = embedding_model.to_string();
+        document.chunk_count = chunks.len() as i64;
+        for chunk in &mut chunks {
+            chunk.collection_id = collection_id.to_string();
+            chunk.id = chunk_id(collection_id, &document.id, chunk.chunk_index as usize, &chunk.content);
+        }
+        store.store_chunks(&document, &chunks)?;
+
+        summary.documents_imported += 1;
+        summary.chunks_imported += chunks.len();
+    }
+
+    Ok(summary)
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<File>> {
+    ZipArchive::new(File::open(path)?).map_err(archive_error)
+}
+
+fn read_manifest_from(archive: &mut ZipArchive<File>) -> Result<ArchiveManifest> {
+    let mut json = String::new();
+    archive.by_name(MANIFEST_ENTRY)
+        .map_err(|_| AppError::File("Not an OmniRecall index archive (manifest.json is missing)".to_string()))?
+        .read_to_string(&mut json)?;
+
+    let manifest: ArchiveManifest = serde_json::from_str(&json)?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(AppError::File(format!(
+            "Archive format v{} is newer than this version of OmniRecall supports (v{})",
+            manifest.format_version, ARCHIVE_FORMAT_VERSION
+        )));
+    }
+
+    Ok(manifest)
+}
+
+fn archive_error(e: zip::result::ZipError) -> AppError {
+    AppError::File(format!("Failed to read or write index archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::quantization::Quantization;
+    use crate::services::vector_store::{IndexStatus, DEFAULT_COLLECTION};
+
+    fn index(store: &VectorStore, collection_id: &str, document_id: &str, texts: &[&str]) {
+        let record = DocumentRecord {
+            id: document_id.to_string(),
+            collection_id: collection_id.to_string(),
+            name: format!("{}.md", document_id),
+            path: format!("/handbook/{}.md", document_id),
+            size: 10,
+            content_hash: "hash".to_string(),
+            mtime: None,
+            chunk_count: texts.len() as i64,
+            embedding_model: "text-embedding-004".to_string(),
+            indexed_at: None,
+            status: IndexStatus::Indexed,
+            last_error: None,
+            chunking_strategy: String::new(),
+        };
+        let chunks: Vec<DocumentChunk> = texts.iter().enumerate().map(|(i, text)| DocumentChunk {
+            id: uuid::Uuid::new_v4().to_string(),
+            collection_id: collection_id.to_string(),
+            document_id: document_id.to_string(),
+            document_name: record.name.clone(),
+            content: text.to_string(),
+            embedding: vec![1.0, i as f32],
+            chunk_index: i as i32,
+            token_count: 2,
+            metadata: Metadata::new(),
+            location: None,
+        }).collect();
+        store.store_chunks(&record, &chunks).unwrap();
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let path = std::env::temp_dir().join(format!("omnirecall-export-{}.zip", uuid::Uuid::new_v4()));
+        let source = VectorStore::open_in_memory().unwrap();
+        index(&source, DEFAULT_COLLECTION, "onboarding", &["Welcome aboard.", "Laptops ship on day one."]);
+        index(&source, DEFAULT_COLLECTION, "leave", &["Holidays accrue monthly."]);
+
+        let manifest = export_collection(&source, DEFAULT_COLLECTION, &path).unwrap();
+        assert_eq!((manifest.document_count, manifest.chunk_count, manifest.dimensions), (2, 3, Some(2)));
+        assert_eq!(read_manifest(&path).unwrap().embedding_model, "text-embedding-004");
+
+        let target = VectorStore::open_in_memory().unwrap();
+        let handbook = target.create_collection("Handbook").unwrap();
+        index(&target, &handbook.id, "leave", &["Old leave policy."]);
+
+        assert!(import_collection(&target, &path, Some(&handbook.id), "text-embedding-3-small", ConflictPolicy::Skip).is_err());
+
+        let summary = import_collection(&target, &path, Some(&handbook.id), "text-embedding-004", ConflictPolicy::Skip).unwrap();
+        assert_eq!((summary.documents_imported, summary.documents_skipped), (1, 1));
+        assert_eq!(target.get_document_chunks(&handbook.id, "leave").unwrap()[0].content, "Old leave policy.");
+
+        let summary = import_collection(&target, &path, Some(&handbook.id), "text-embedding-004", ConflictPolicy::Replace).unwrap();
+        assert_eq!(summary.documents_replaced, 2);
+        assert_eq!(target.get_document_chunks(&handbook.id, "leave").unwrap()[0].content, "Holidays accrue monthly.");
+
+        let summary = import_collection(&target, &path, Some(&handbook.id), "text-embedding-004", ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(summary.documents_renamed, 2);
+        assert_eq!(target.get_document_count(&handbook.id).unwrap(), 4);
+        assert_eq!(target.get_chunk_count(&handbook.id).unwrap(), 6);
+        // The source chunks were never touched
+        assert_eq!(source.get_chunk_count(DEFAULT_COLLECTION).unwrap(), 3);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_import_into_a_new_collection() {
+        let path = std::env::temp_dir().join(format!("omnirecall-new-{}.zip", uuid::Uuid::new_v4()));
+        let source = VectorStore::open_in_memory().unwrap().with_quantization(Quantization::Binary);
+        let handbook = source.create_collection("Handbook").unwrap();
+        index(&source, &handbook.id, "leave", &["Holidays accrue monthly."]);
+        export_collection(&source, &handbook.id, &path).unwrap();
+
+        // A rejected archive leaves no empty collection behind
+        let target = VectorStore::open_in_memory().unwrap();
+        let collections = target.list_collections().unwrap().len();
+        assert!(import_collection(&target, &path, None, "text-embedding-3-small", ConflictPolicy::Skip).is_err());
+        assert_eq!(target.list_collections().unwrap().len(), collections);
+
+        let summary = import_collection(&target, &path, None, "text-embedding-004", ConflictPolicy::Skip).unwrap();
+        let imported = target.list_collections().unwrap().into_iter().find(|c| c.id == summary.collection_id).unwrap();
+        assert_eq!(imported.name, "Handbook");
+        // Exported at full precision even though the source stores sign bits
+        assert_eq!(target.get_document_chunks(&imported.id, "leave").unwrap()[0].embedding, vec![1.0, 0.0]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_import_rejects_chunks_without_a_document() {
+        let path = std::env::temp_dir().join(format!("omnirecall-orphans-{}.zip", uuid::Uuid::new_v4()));
+        let source = VectorStore::open_in_memory().unwrap();
+        index(&source, DEFAULT_COLLECTION, "onboarding", &["Welcome aboard."]);
+        export_collection(&source, DEFAULT_COLLECTION, &path).unwrap();
+
+        // Rewrite the archive with an empty document registry
+        let mut archive = open_archive(&path).unwrap();
+        let entries: Vec<(String, String)> = (0..archive.len())
+            .map(|i| {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                let content = if entry.name() == DOCUMENTS_ENTRY { "[]".to_string() } else { content };
+                (entry.name().to_string(), content)
+            })
+            .collect();
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let target = VectorStore::open_in_memory().unwrap();
+        assert!(import_collection(&target, &path, Some(DEFAULT_COLLECTION), "text-embedding-004", ConflictPolicy::Skip).is_err());
+        assert_eq!(target.get_chunk_count(DEFAULT_COLLECTION).unwrap(), 0);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
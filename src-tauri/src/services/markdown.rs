@@ -0,0 +1,188 @@
+/// Markdown assembled block by block, for extractors that turn structured
+/// documents into text the chunker can split along headings and tables
+#[derive(Default)]
+pub struct MarkdownWriter {
+    text: String,
+    /// Column where the text of the open list item at each depth starts,
+    /// so nested items line up under their parent. Empty unless the last
+    /// block was a list item, in which case the next item follows it
+    /// without a blank line.
+    list_indents: Vec<usize>,
+    /// Whether a list item of the open list has more than one paragraph, so
+    /// its items are separated by blank lines
+    loose_list: bool,
+}
+
+/// How a list item is marked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListMarker {
+    Bullet,
+    Number(usize),
+}
+
+impl MarkdownWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heading(&mut self, level: usize, text: &str) {
+        self.block(&format!("{} {}", "#".repeat(level.clamp(1, 6)), text));
+    }
+
+    pub fn paragraph(&mut self, text: &str) {
+        // A paragraph starting with `#` would read as a heading
+        if text.starts_with('#') {
+            self.block(&format!("\\{}", text));
+        } else {
+            self.block(text);
+        }
+    }
+
+    /// A bulleted or numbered list item at `depth` (0 for top-level items)
+    pub fn list_item(&mut self, depth: usize, marker: ListMarker, text: &str) {
+        let marker = match marker {
+            ListMarker::Bullet => "-".to_string(),
+            ListMarker::Number(number) => format!("{}.", number),
+        };
+        let indent = self.list_indent(depth);
+        let item = format!("{}{} {}", " ".repeat(indent), marker, text);
+        if self.list_indents.is_empty() {
+            self.block(&item);
+        } else {
+            self.text.push_str(if self.loose_list { "\n\n" } else { "\n" });
+            self.text.push_str(&item);
+        }
+
+        // Levels skipped over get the indent of a bullet
+        self.list_indents.truncate(depth);
+        while self.list_indents.len() < depth {
+            self.list_indents.push(self.list_indents.last().unwrap_or(&0) + 2);
+        }
+        self.list_indents.push(indent + marker.len() + 1);
+    }
+
+    /// A further paragraph of the open list item at `depth`, or a plain
+    /// paragraph if there is none
+    pub fn list_continuation(&mut self, depth: usize, text: &str) {
+        let Some(&indent) = self.list_indents.get(depth) else {
+            self.paragraph(text);
+            return;
+        };
+        self.text.push_str("\n\n");
+        self.text.push_str(&" ".repeat(indent));
+        self.text.push_str(text);
+        self.list_indents.truncate(depth + 1);
+        self.loose_list = true;
+    }
+
+    /// A fenced code block, tagged with `language` if known
+    pub fn code_block(&mut self, language: Option<&str>, code: &str) {
+        let code = code.trim_matches('\n');
+        if code.trim().is_empty() {
+            return;
+        }
+        // Code that itself contains a backtick fence gets a tilde one
+        let fence = if code.contains("```") { "~~~" } else { "```" };
+        self.block(&format!("{}{}\n{}\n{}", fence, language.unwrap_or_default(), code, fence));
+    }
+
+    /// A table whose first row is the header. Empty tables are left out.
+    pub fn table(&mut self, rows: &[Vec<String>]) {
+        let table = table(rows);
+        if !table.is_empty() {
+            self.block(&table);
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+
+    fn block(&mut self, block: &str) {
+        if !self.text.is_empty() {
+            self.text.push_str("\n\n");
+        }
+        self.text.push_str(block);
+        self.list_indents.clear();
+        self.loose_list = false;
+    }
+
+    /// Indent of a list item at `depth`: that of its parent item's text
+    fn list_indent(&self, depth: usize) -> usize {
+        match depth.checked_sub(1) {
+            None => 0,
+            Some(parent) => match self.list_indents.get(parent) {
+                Some(&indent) => indent,
+                None => self.list_indents.last().unwrap_or(&0) + 2 * (depth - self.list_indents.len()),
+            },
+        }
+    }
+}
+
+/// Render rows as a Markdown table with the first row as the header. Rows
+/// are padded to the widest one; empty rows are dropped.
+pub fn table(rows: &[Vec<String>]) -> String {
+    let rows: Vec<&Vec<String>> = rows.iter().filter(|row| row.iter().any(|cell| !cell.trim().is_empty())).collect();
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+
+    let line = |row: &[String]| {
+        let cells: Vec<String> = (0..columns)
+            .map(|i| row.get(i).map_or(String::new(), |cell| table_cell(cell)))
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+
+    let mut lines = vec![line(rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// Cell text on one line, with pipes escaped
+fn table_cell(cell: &str) -> String {
+    cell.split_whitespace().collect::<Vec<_>>().join(" ").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_separates_blocks() {
+        let mut markdown = MarkdownWriter::new();
+        markdown.heading(2, "Results");
+        markdown.paragraph("#1 seed won.");
+        markdown.list_item(0, ListMarker::Bullet, "First");
+        markdown.list_item(1, ListMarker::Bullet, "Nested");
+        markdown.table(&[
+            vec!["Name".to_string(), "Notes".to_string()],
+            vec![String::new(), String::new()],
+            vec!["a|b".to_string(), "two\nlines".to_string(), "extra".to_string()],
+        ]);
+
+        assert_eq!(
+            markdown.finish(),
+            "## Results\n\n\\#1 seed won.\n\n- First\n  - Nested\n\n\
+             | Name | Notes |  |\n| --- | --- | --- |\n| a\\|b | two lines | extra |"
+        );
+    }
+
+    #[test]
+    fn test_writer_numbers_lists() {
+        let mut markdown = MarkdownWriter::new();
+        markdown.list_item(0, ListMarker::Number(9), "Ninth");
+        markdown.list_item(1, ListMarker::Bullet, "Detail");
+        markdown.list_continuation(0, "More on the ninth.");
+        markdown.list_item(0, ListMarker::Number(10), "Tenth");
+        markdown.list_item(1, ListMarker::Number(1), "Step");
+        markdown.list_item(2, ListMarker::Bullet, "Note");
+        markdown.paragraph("Done.");
+
+        assert_eq!(
+            markdown.finish(),
+            "9. Ninth\n   - Detail\n\n   More on the ninth.\n\n10. Tenth\n\n    1. Step\n\n       - Note\n\nDone."
+        );
+    }
+}
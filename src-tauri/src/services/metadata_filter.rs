@@ -0,0 +1,371 @@
+use rusqlite::types::Value;
+use crate::error::{AppError, Result};
+
+/// A parsed metadata filter expression, e.g.
+/// `tag = "legal" AND (author = 'Ada' OR modified > 2026-01-01)`.
+///
+/// Fields are looked up in the chunk's JSON metadata. `tag`/`tags` match if
+/// the `tags` array contains the value, and `heading`/`headings` if any
+/// heading above the chunk does. `page` matches chunks whose pages include
+/// the value (`page > 3` if any of them is past page 3); chunks without
+/// pages never match. Supported operators are `=`, `!=`,
+/// `<`, `<=`, `>`, `>=` and `~` (substring match); expressions combine with
+/// `AND`, `OR`, `NOT` and parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataFilter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare { field: String, op: Op, value: Literal },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Text(String),
+    Number(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl MetadataFilter {
+    /// Parse a filter expression
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(filter_error("expression is empty"));
+        }
+
+        let mut parser = Parser { tokens, pos: 0, depth: 0, conditions: 0 };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(filter_error(&format!("unexpected {:?}", parser.tokens[parser.pos])));
+        }
+
+        Ok(Self { expr })
+    }
+
+    /// Render the filter as an SQL condition over `c.metadata`. Placeholders
+    /// are numbered from `first_param`, so the fragment can be appended to a
+    /// query that already binds `?1..?(first_param - 1)`.
+    pub fn to_sql(&self, first_param: usize) -> (String, Vec<Value>) {
+        let mut params = Vec::new();
+        let sql = render(&self.expr, first_param, &mut params);
+        (sql, params)
+    }
+}
+
+fn filter_error(msg: &str) -> AppError {
+    AppError::Config(format!("Invalid metadata filter: {}", msg))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(ch) if ch == c => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err(filter_error("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                chars.next();
+                let followed_by_eq = chars.peek() == Some(&'=');
+                let op = match (c, followed_by_eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', true) => Op::Le,
+                    ('<', false) => Op::Lt,
+                    ('>', true) => Op::Ge,
+                    ('>', false) => Op::Gt,
+                    ('~', _) => Op::Contains,
+                    _ => return Err(filter_error("expected '!='")),
+                };
+                if followed_by_eq && c != '~' {
+                    chars.next();
+                }
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.' | ':' | '+') {
+                        word.push(ch);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if word.is_empty() {
+                    return Err(filter_error(&format!("unexpected character '{}'", c)));
+                }
+                tokens.push(match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Ident(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// How deeply `NOT` and parentheses may nest, and how many comparisons one
+/// filter may hold. Parsing and rendering both recurse, so unbounded input
+/// could overflow the stack.
+const MAX_NESTING: usize = 64;
+const MAX_CONDITIONS: usize = 256;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    conditions: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_NESTING {
+            return Err(filter_error(&format!("nested more than {} levels deep", MAX_NESTING)));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.nested(Self::parse_unary)?))),
+            Some(Token::LParen) => {
+                let expr = self.nested(Self::parse_or)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(filter_error("missing ')'")),
+                }
+            }
+            Some(Token::Ident(field)) => {
+                self.conditions += 1;
+                if self.conditions > MAX_CONDITIONS {
+                    return Err(filter_error(&format!("more than {} conditions", MAX_CONDITIONS)));
+                }
+                if !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(filter_error(&format!("invalid field name '{}'", field)));
+                }
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(filter_error(&format!("expected an operator after '{}'", field))),
+                };
+                let value = match self.next() {
+                    Some(Token::Text(text)) => Literal::Text(text),
+                    // Bare words are numbers if they parse as one, otherwise
+                    // text (dates like 2026-01-01 compare as ISO strings).
+                    Some(Token::Ident(word)) => word.parse::<f64>()
+                        .map(Literal::Number)
+                        .unwrap_or(Literal::Text(word)),
+                    _ => return Err(filter_error(&format!("expected a value after '{}'", field))),
+                };
+                Ok(Expr::Compare { field: field.to_lowercase(), op, value })
+            }
+            other => Err(filter_error(&format!("unexpected {:?}", other))),
+        }
+    }
+}
+
+fn render(expr: &Expr, first_param: usize, params: &mut Vec<Value>) -> String {
+    match expr {
+        Expr::And(a, b) => format!(
+            "({} AND {})",
+            render(a, first_param, params),
+            render(b, first_param, params)
+        ),
+        Expr::Or(a, b) => format!(
+            "({} OR {})",
+            render(a, first_param, params),
+            render(b, first_param, params)
+        ),
+        Expr::Not(inner) => format!("(NOT {})", render(inner, first_param, params)),
+        Expr::Compare { field, op, value } => {
+            let placeholder = format!("?{}", first_param + params.len());
+            params.push(match (op, value) {
+                (Op::Contains, Literal::Text(t)) => Value::Text(format!("%{}%", t)),
+                (Op::Contains, Literal::Number(n)) => Value::Text(format!("%{}%", n)),
+                (_, Literal::Text(t)) => Value::Text(t.clone()),
+                (_, Literal::Number(n)) => Value::Real(*n),
+            });
+
+            let array = match field.as_str() {
+                "tag" | "tags" => Some("tags"),
+                "heading" | "headings" => Some("headings"),
+                _ => None,
+            };
+            if let Some(array) = array {
+                let exists = format!(
+                    "EXISTS (SELECT 1 FROM json_each(c.metadata, '$.{}') WHERE value {} {})",
+                    array,
+                    if *op == Op::Contains { "LIKE" } else { "=" },
+                    placeholder
+                );
+                return if *op == Op::Ne { format!("(NOT {})", exists) } else { exists };
+            }
+
+            // Pages are stored as the chunk's first and last page
+            if field == "page" {
+                let on_page = format!("(c.page_start <= {0} AND c.page_end >= {0})", placeholder);
+                return match op {
+                    Op::Eq => on_page,
+                    Op::Ne => format!("(c.page_start IS NOT NULL AND NOT {})", on_page),
+                    Op::Lt => format!("c.page_start < {}", placeholder),
+                    Op::Le => format!("c.page_start <= {}", placeholder),
+                    Op::Gt => format!("c.page_end > {}", placeholder),
+                    Op::Ge => format!("c.page_end >= {}", placeholder),
+                    Op::Contains => format!(
+                        "(CAST(c.page_start AS TEXT) LIKE {0} OR CAST(c.page_end AS TEXT) LIKE {0})",
+                        placeholder
+                    ),
+                };
+            }
+
+            let column = format!("json_extract(c.metadata, '$.{}')", field);
+            match op {
+                Op::Eq => format!("{} = {}", column, placeholder),
+                // Missing fields count as "not equal"
+                Op::Ne => format!("{} IS NOT {}", column, placeholder),
+                Op::Lt => format!("{} < {}", column, placeholder),
+                Op::Le => format!("{} <= {}", column, placeholder),
+                Op::Gt => format!("{} > {}", column, placeholder),
+                Op::Ge => format!("{} >= {}", column, placeholder),
+                Op::Contains => format!("{} LIKE {}", column, placeholder),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render() {
+        let filter = MetadataFilter::parse(r#"tag = "legal" AND (author != 'Ada' OR modified > 2026-01-01)"#).unwrap();
+        let (sql, params) = filter.to_sql(3);
+
+        assert_eq!(
+            sql,
+            "(EXISTS (SELECT 1 FROM json_each(c.metadata, '$.tags') WHERE value = ?3) AND \
+             (json_extract(c.metadata, '$.author') IS NOT ?4 OR json_extract(c.metadata, '$.modified') > ?5))"
+        );
+        assert_eq!(params, vec![
+            Value::Text("legal".to_string()),
+            Value::Text("Ada".to_string()),
+            Value::Text("2026-01-01".to_string()),
+        ]);
+
+        let (sql, params) = MetadataFilter::parse("page >= 14 AND heading ~ Install").unwrap().to_sql(1);
+        assert_eq!(
+            sql,
+"(c.page_end >= ?1 AND EXISTS (SELECT 1 FROM json_each(c.metadata, '$.headings') WHERE value LIKE ?2))"
+        );
+        assert_eq!(params, vec![Value::Real(14.0), Value::Text("%Install%".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(MetadataFilter::parse("").is_err());
+        assert!(MetadataFilter::parse("tag =").is_err());
+        assert!(MetadataFilter::parse("tag = 'legal").is_err());
+        assert!(MetadataFilter::parse("(tag = legal").is_err());
+        assert!(MetadataFilter::parse("a.b = 1").is_err());
+        assert!(MetadataFilter::parse("tag = legal extra").is_err());
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let nested = |depth: usize| format!("{}tag = a{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(MetadataFilter::parse(&nested(MAX_NESTING)).is_ok());
+        assert!(MetadataFilter::parse(&nested(MAX_NESTING + 1)).is_err());
+        assert!(MetadataFilter::parse(&nested(100_000)).is_err());
+        assert!(MetadataFilter::parse(&format!("{}tag = a", "NOT ".repeat(100_000))).is_err());
+
+        let chain = |n: usize| vec!["tag = a"; n].join(" AND ");
+        assert!(MetadataFilter::parse(&chain(MAX_CONDITIONS)).is_ok());
+        assert!(MetadataFilter::parse(&chain(MAX_CONDITIONS + 1)).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use rusqlite::{Connection, Transaction};
+use std::path::Path;
+use crate::error::{AppError, Result};
+use crate::services::quantization;
+
+/// A single, ordered schema upgrade step. Each step runs in its own
+/// transaction together with the `user_version` bump, so a failure leaves
+/// the database at the previous version.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Upgrade steps for `vectors.db`, in order. Never edit a released step;
+/// append a new one instead.
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "chunks table", apply: create_chunks },
+    Migration { version: 2, description: "collections", apply: add_collections },
+    Migration { version: 3, description: "keyword index", apply: add_keyword_index },
+    Migration { version: 4, description: "chunk metadata", apply: add_chunk_metadata },
+    Migration { version: 5, description: "document registry", apply: add_document_registry },
+    Migration { version: 6, description: "normalized and quantized embeddings", apply: add_embedding_format },
+    Migration { version: 7, description: "chunking strategy", apply: add_chunking_strategy },
+    Migration { version: 8, description: "chunk locations", apply: add_chunk_locations },
+];
+
+/// Chunks normalized per query by the embedding format migration
+const NORMALIZE_BATCH: i64 = 1000;
+
+/// Schema version this build of the app reads and writes
+pub const SCHEMA_VERSION: u32 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Bring the database up to `SCHEMA_VERSION`.
+///
+/// Existing databases are copied to `<db>.v<old>.bak` before the first step
+/// runs, if a `db_path` is given. Databases written by a newer app version
+/// are refused rather than opened with a schema we don't understand.
+pub fn migrate(conn: &mut Connection, db_path: Option<&Path>) -> Result<()> {
+    let current = schema_version(conn)?;
+
+    if current > SCHEMA_VERSION {
+        return Err(AppError::Database(format!(
+            "The index was created by a newer version of OmniRecall (schema v{}, this version supports v{}). Please update the app.",
+            current, SCHEMA_VERSION,
+        )));
+    }
+    if current == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if let Some(db_path) = db_path {
+        if has_tables(conn)? {
+            backup(conn, db_path, current)?;
+        }
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        tracing::info!("Migrating vector store to v{} ({})", migration.version, migration.description);
+
+        let tx = conn.transaction()
+            .map_err(|e| AppError::Database(format!("Failed to start migration: {}", e)))?;
+        (migration.apply)(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+            .map_err(|e| AppError::Database(format!(
+                "Migration to v{} ({}) failed: {}",
+                migration.version, migration.description, e,
+            )))?;
+        tx.commit()
+            .map_err(|e| AppError::Database(format!("Failed to commit migration: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| AppError::Database(format!("Failed to read schema version: {}", e)))
+}
+
+fn has_tables(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| AppError::Database(format!("Failed to inspect schema: {}", e)))?;
+
+    Ok(count > 0)
+}
+
+fn backup(conn: &Connection, db_path: &Path, version: u32) -> Result<()> {
+    let mut backup_path = db_path.as_os_str().to_owned();
+    backup_path.push(format!(".v{}.bak", version));
+    let backup_path = Path::new(&backup_path);
+
+    if backup_path.exists() {
+        std::fs::remove_file(backup_path)?;
+    }
+
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| AppError::Database(format!("Failed to back up database before migrating: {}", e)))?;
+
+    Ok(())
+}
+
+/// Some pre-versioning databases already have a column a step adds
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table))
+        .is_ok();
+    if !exists {
+        tx.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition))?;
+    }
+    Ok(())
+}
+
+fn create_chunks(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS chunks (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL,
+            document_name TEXT NOT NULL,
+            content TEXT NOT NULL,
+            embedding BLOB NOT NULL,
+            chunk_index INTEGER NOT NULL,
+            token_count INTEGER NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_chunks_document_id ON chunks(document_id);
+        CREATE INDEX IF NOT EXISTS idx_chunks_document_name ON chunks(document_name);"
+    )
+}
+
+fn add_collections(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS collections (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        INSERT OR IGNORE INTO collections (id, name) VALUES ('default', 'Default');"
+    )?;
+
+    // Everything indexed before collections existed belongs to the default one
+    add_column_if_missing(tx, "chunks", "collection_id", "TEXT NOT NULL DEFAULT 'default'")?;
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS idx_chunks_collection_id ON chunks(collection_id);")
+}
+
+fn add_keyword_index(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
+            chunk_id UNINDEXED,
+            content
+        );
+
+        INSERT INTO chunks_fts (chunk_id, content)
+        SELECT id, content FROM chunks
+        WHERE id NOT IN (SELECT chunk_id FROM chunks_fts);"
+    )
+}
+
+fn add_chunk_metadata(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "chunks", "metadata", "TEXT NOT NULL DEFAULT '{}'")
+}
+
+fn add_document_registry(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS documents (
+            id TEXT NOT NULL,
+            collection_id TEXT NOT NULL DEFAULT 'default',
+            name TEXT NOT NULL,
+            path TEXT NOT NULL DEFAULT '',
+            size INTEGER NOT NULL DEFAULT 0,
+            content_hash TEXT NOT NULL DEFAULT '',
+            mtime TEXT,
+            chunk_count INTEGER NOT NULL DEFAULT 0,
+            embedding_model TEXT NOT NULL DEFAULT '',
+            indexed_at TEXT,
+            status TEXT NOT NULL DEFAULT 'pending',
+            last_error TEXT,
+            PRIMARY KEY (collection_id, id)
+        );
+
+        -- Register documents whose chunks were stored before the registry existed
+        INSERT OR IGNORE INTO documents (id, collection_id, name, path, chunk_count, indexed_at, status)
+        SELECT document_id, collection_id, document_name,
+               COALESCE(json_extract(MIN(metadata), '$.file_path'), ''),
+               COUNT(*), MAX(created_at), 'indexed'
+        FROM chunks
+        GROUP BY collection_id, document_id;"
+    )
+}
+
+fn add_embedding_format(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "chunks", "embedding_format", "TEXT NOT NULL DEFAULT 'f32'")?;
+    // Full-precision vectors of quantized chunks, for rescoring
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS chunk_embeddings (
+            chunk_id TEXT PRIMARY KEY,
+            embedding BLOB NOT NULL
+        );"
+    )?;
+
+    // Scoring is now a dot product, so existing vectors must be unit length.
+    // Work in batches so memory use stays flat on large indexes.
+    let mut select = tx.prepare("SELECT rowid, embedding FROM chunks WHERE rowid > ?1 ORDER BY rowid LIMIT ?2")?;
+    let mut update = tx.prepare("UPDATE chunks SET embedding = ?2 WHERE rowid = ?1")?;
+    let mut last_rowid = 0i64;
+    loop {
+        let batch = select
+            .query_map(rusqlite::params![last_rowid, NORMALIZE_BATCH], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let Some(&(rowid, _)) = batch.last() else {
+            break;
+        };
+        last_rowid = rowid;
+
+        for (rowid, bytes) in batch {
+            let mut embedding = quantization::from_bytes(&bytes);
+            quantization::normalize(&mut embedding);
+            update.execute(rusqlite::params![rowid, quantization::to_bytes(&embedding)])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_chunking_strategy(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "documents", "chunking_strategy", "TEXT NOT NULL DEFAULT ''")
+}
+
+/// Nullable, since chunks indexed earlier have no recorded location
+fn add_chunk_locations(tx: &Transaction) -> rusqlite::Result<()> {
+    for column in ["char_start", "char_end", "line_start", "line_end", "page_start", "page_end"] {
+        add_column_if_missing(tx, "chunks", column, "INTEGER")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrates_legacy_database() {
+        let dir = std::env::temp_dir().join(format!("omnirecall-migrate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("vectors.db");
+
+        // A database as written before schema versioning existed
+        let mut conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chunks (
+                id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                document_name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                token_count INTEGER NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO chunks (id, document_id, document_name, content, embedding, chunk_index, token_count)
+            VALUES ('c1', 'd1', 'notes.md', 'hello world', x'0000404000008040', 0, 3);"
+        ).unwrap();
+
+        migrate(&mut conn, Some(&db_path)).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert!(dir.join("vectors.db.v0.bak").exists());
+        let (collection, document_count, fts_count): (String, i64, i64) = conn.query_row(
+            "SELECT (SELECT collection_id FROM chunks WHERE id = 'c1'),
+                    (SELECT COUNT(*) FROM documents),
+                    (SELECT COUNT(*) FROM chunks_fts WHERE chunks_fts MATCH 'hello')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!((collection.as_str(), document_count, fts_count), ("default", 1, 1));
+
+        // [3.0, 4.0] is rescaled to unit length
+        let embedding: Vec<u8> = conn.query_row("SELECT embedding FROM chunks WHERE id = 'c1'", [], |row| row.get(0)).unwrap();
+        assert_eq!(quantization::from_bytes(&embedding), vec![0.6, 0.8]);
+
+        // Re-running is a no-op
+        migrate(&mut conn, Some(&db_path)).unwrap();
+
+        drop(conn);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+
+        assert!(migrate(&mut conn, None).is_err());
+    }
+}
@@ -1,4 +1,17 @@
 pub mod ai_client;
+pub mod diversity;
 pub mod embedding;
+pub mod epub;
+pub mod html;
+pub mod index_archive;
 pub mod document_pipeline;
+pub mod markdown;
+pub mod metadata_filter;
+pub mod migrations;
+pub mod office_documents;
+pub mod quantization;
+pub mod query_expansion;
+pub mod reranker;
+pub mod semantic_chunker;
+pub mod vector_backend;
 pub mod vector_store;
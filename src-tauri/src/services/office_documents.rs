@@ -0,0 +1,862 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use calamine::{open_workbook, Data, DataType, Reader as _, Xlsx};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use zip::result::ZipError;
+use zip::ZipArchive;
+use crate::error::{AppError, Result};
+use crate::services::markdown::{ListMarker, MarkdownWriter};
+
+/// Largest uncompressed XML part read from an Office file. The files
+/// themselves are size-checked before extraction, but a zip member can
+/// expand to far more than its compressed size.
+const MAX_PART_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Placeholders whose text is page furniture rather than content
+const SKIPPED_PLACEHOLDERS: &[&str] = &["sldNum", "dt", "ftr", "hdr", "sldImg"];
+
+/// Convert a Word document to Markdown. Headings come from the paragraph
+/// styles (or outline levels), numbered and bulleted paragraphs become list
+/// items, and tables are kept as tables.
+pub fn docx_to_markdown(path: &Path) -> Result<String> {
+    let mut archive = open_archive(path)?;
+    let styles = match read_part(&mut archive, "word/styles.xml")? {
+        Some(xml) => docx_heading_styles(&xml)?,
+        None => HashMap::new(),
+    };
+    let numbering = match read_part(&mut archive, "word/numbering.xml")? {
+        Some(xml) => docx_numbering(&xml)?,
+        None => HashMap::new(),
+    };
+    let document = read_part(&mut archive, "word/document.xml")?
+        .ok_or_else(|| AppError::File("Not a Word document: word/document.xml is missing".to_string()))?;
+
+    let mut reader = Reader::from_str(&document);
+    let mut builder = TextBuilder::default();
+    let mut in_text = false;
+    let mut list_level = 0;
+    // Items seen so far at each level of each list, by numbering ID
+    let mut list_counts: HashMap<String, Vec<usize>> = HashMap::new();
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"p" => builder.start_paragraph(),
+                b"t" => in_text = true,
+                b"numPr" => {
+                    list_level = 0;
+                    builder.set_list_depth(Some(0));
+                }
+                b"tbl" => builder.start_table(),
+                b"tr" => builder.start_row(),
+                b"tc" => builder.start_cell(),
+                // Fallback content repeats the preferred version of a drawing
+                b"Fallback" => {
+                    reader.read_to_end(e.name()).map_err(xml_error)?;
+                }
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"pStyle" => {
+                    let level = attribute(&e, b"w:val")
+                        .and_then(|style| styles.get(&style).copied().or_else(|| heading_level(&style)));
+                    if let Some(level) = level {
+                        builder.set_heading(level);
+                    }
+                }
+                b"outlineLvl" => {
+                    if let Some(level) = outline_level(&e) {
+                        builder.set_heading(level);
+                    }
+                }
+                b"ilvl" => {
+                    list_level = attribute(&e, b"w:val").and_then(|l| l.parse().ok()).unwrap_or(0);
+                    builder.set_list_depth(Some(list_level));
+                }
+                b"numId" => match attribute(&e, b"w:val") {
+                    // Numbering ID 0 removes inherited numbering
+                    Some(id) if id == "0" => builder.set_list_depth(None),
+                    Some(id) => {
+                        // An item restarts the numbering of the levels below it
+                        let counts = list_counts.entry(id.clone()).or_default();
+                        counts.resize(list_level + 1, 0);
+                        counts[list_level] += 1;
+                        let start = numbering.get(&id).and_then(|levels| levels.get(&list_level));
+                        builder.set_list_number(start.map(|start| start + counts[list_level] - 1));
+                    }
+                    None => {}
+                },
+                b"tab" | b"br" | b"cr" => builder.text(" "),
+                _ => {}
+            },
+            Event::Text(e) if in_text => builder.text(&e.unescape().map_err(xml_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"p" => builder.end_paragraph(),
+                b"t" => in_text = false,
+                b"tbl" => builder.end_table(),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+/// Heading level of each paragraph style that is a heading, by style ID
+fn docx_heading_styles(xml: &str) -> Result<HashMap<String, usize>> {
+    let mut reader = Reader::from_str(xml);
+    let mut levels = HashMap::new();
+    let mut style_id: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) if e.local_name().as_ref() == b"style" => style_id = attribute(&e, b"w:styleId"),
+            Event::Empty(e) => {
+                let level = match e.local_name().as_ref() {
+                    b"name" => attribute(&e, b"w:val").and_then(|name| heading_level(&name)),
+                    b"outlineLvl" => outline_level(&e),
+                    _ => None,
+                };
+                if let (Some(id), Some(level)) = (&style_id, level) {
+                    levels.insert(id.clone(), level);
+                }
+            }
+            Event::End(e) if e.local_name().as_ref() == b"style" => style_id = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(levels)
+}
+
+/// Start number of each list level that is numbered rather than bulleted,
+/// by numbering ID and level
+fn docx_numbering(xml: &str) -> Result<HashMap<String, HashMap<usize, usize>>> {
+    let mut reader = Reader::from_str(xml);
+    // Numbered levels of the abstract definitions that numbering IDs refer to
+    let mut abstract_levels: HashMap<String, HashMap<usize, usize>> = HashMap::new();
+    let mut abstract_id: Option<String> = None;
+    // Level being read, with its start number and whether it is numbered
+    let mut level: Option<(usize, usize, bool)> = None;
+    let mut num_id: Option<String> = None;
+    let mut numbering = HashMap::new();
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"abstractNum" => abstract_id = attribute(&e, b"w:abstractNumId"),
+                b"lvl" => level = attribute(&e, b"w:ilvl").and_then(|l| l.parse().ok()).map(|l| (l, 1, true)),
+                b"num" => num_id = attribute(&e, b"w:numId"),
+                _ => {}
+            },
+            Event::Empty(e) => match (e.local_name().as_ref(), level.as_mut()) {
+                (b"start", Some((_, start, _))) => {
+                    *start = attribute(&e, b"w:val").and_then(|s| s.parse().ok()).unwrap_or(1);
+                }
+                (b"numFmt", Some((_, _, numbered))) => {
+                    *numbered = !matches!(attribute(&e, b"w:val").as_deref(), Some("bullet" | "none"));
+                }
+                (b"abstractNumId", None) => {
+                    if let (Some(id), Some(levels)) = (&num_id, attribute(&e, b"w:val").and_then(|a| abstract_levels.get(&a))) {
+                        numbering.insert(id.clone(), levels.clone());
+                    }
+                }
+                _ => {}
+            },
+            Event::End(e) => match e.local_name().as_ref() {
+                b"lvl" => {
+                    if let (Some(id), Some((level, start, true))) = (&abstract_id, level.take()) {
+                        abstract_levels.entry(id.clone()).or_default().insert(level, start);
+                    }
+                }
+                b"abstractNum" => abstract_id = None,
+                b"num" => num_id = None,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(numbering)
+}
+
+/// Heading level named by a style such as "heading 2", "Heading2" or "Title"
+fn heading_level(style: &str) -> Option<usize> {
+    let style = style.to_ascii_lowercase().replace(' ', "");
+    if style == "title" {
+        return Some(1);
+    }
+    style.strip_prefix("heading")?.parse().ok().filter(|level| (1..=9).contains(level))
+}
+
+/// Heading level of a `w:outlineLvl` element (0-based, with 9 meaning body text)
+fn outline_level(e: &BytesStart) -> Option<usize> {
+    attribute(e, b"w:val")?.parse::<usize>().ok().filter(|level| *level < 9).map(|level| level + 1)
+}
+
+/// Convert an OpenDocument text file to Markdown, with its headings, lists
+/// and tables
+pub fn odt_to_markdown(path: &Path) -> Result<String> {
+    let mut archive = open_archive(path)?;
+    let content = read_part(&mut archive, "content.xml")?
+        .ok_or_else(|| AppError::File("Not an OpenDocument file: content.xml is missing".to_string()))?;
+    let mut list_styles = odt_list_styles(&content)?;
+    if let Some(xml) = read_part(&mut archive, "styles.xml")? {
+        list_styles.extend(odt_list_styles(&xml)?);
+    }
+
+    let mut reader = Reader::from_str(&content);
+    let mut builder = TextBuilder::default();
+    let mut lists: Vec<OdtList> = Vec::new();
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"h" => {
+                    builder.start_paragraph();
+                    let level = attribute(&e, b"text:outline-level").and_then(|l| l.parse().ok());
+                    builder.set_heading(level.unwrap_or(1));
+                }
+                b"p" => {
+                    builder.start_paragraph();
+                    let depth = lists.len();
+                    if let Some(list) = lists.last_mut() {
+                        builder.set_list_depth(Some(depth - 1));
+                        if list.item_started {
+                            builder.set_continues_item();
+                        } else {
+                            list.item_started = true;
+                            let start = list.style.as_ref().and_then(|style| list_styles.get(style)?.get(&depth));
+                            builder.set_list_number(start.map(|start| start + list.items.saturating_sub(1)));
+                        }
+                    }
+                }
+                b"list" => {
+                    // Nested lists take their style from the outer list
+                    let style = attribute(&e, b"text:style-name").or_else(|| lists.last().and_then(|list| list.style.clone()));
+                    lists.push(OdtList { style, items: 0, item_started: false });
+                }
+                b"list-item" => {
+                    if let Some(list) = lists.last_mut() {
+                        list.items += 1;
+                        list.item_started = false;
+                    }
+                }
+                // A list header's paragraphs are not an item
+                b"list-header" => {
+                    if let Some(list) = lists.last_mut() {
+                        list.item_started = true;
+                    }
+                }
+                b"table" => builder.start_table(),
+                b"table-row" => builder.start_row(),
+                b"table-cell" | b"covered-table-cell" => builder.start_cell(),
+                // Comments and deleted text are not part of the document
+                b"annotation" | b"tracked-changes" => {
+                    reader.read_to_end(e.name()).map_err(xml_error)?;
+                }
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"s" | b"tab" | b"line-break" => builder.text(" "),
+                b"table-cell" | b"covered-table-cell" => builder.start_cell(),
+                _ => {}
+            },
+            Event::Text(e) => builder.text(&e.unescape().map_err(xml_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"h" | b"p" => builder.end_paragraph(),
+                b"list" => {
+                    lists.pop();
+                }
+                b"table" => builder.end_table(),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+/// An open `text:list` of an OpenDocument file
+struct OdtList {
+    style: Option<String>,
+    /// Items started so far
+    items: usize,
+    /// Whether the current item's first paragraph has been read
+    item_started: bool,
+}
+
+/// Start number of each list level that is numbered rather than bulleted,
+/// by list style name and level (1-based, as in the file)
+fn odt_list_styles(xml: &str) -> Result<HashMap<String, HashMap<usize, usize>>> {
+    let mut reader = Reader::from_str(xml);
+    let mut styles: HashMap<String, HashMap<usize, usize>> = HashMap::new();
+    let mut style_name: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"list-style" => style_name = attribute(&e, b"style:name"),
+                b"list-level-style-number" => {
+                    let level = attribute(&e, b"text:level").and_then(|l| l.parse().ok());
+                    let start = attribute(&e, b"text:start-value").and_then(|s| s.parse().ok()).unwrap_or(1);
+                    if let (Some(name), Some(level)) = (&style_name, level) {
+                        styles.entry(name.clone()).or_default().insert(level, start);
+                    }
+                }
+                _ => {}
+            },
+            Event::End(e) if e.local_name().as_ref() == b"list-style" => style_name = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(styles)
+}
+
+/// Convert a PowerPoint presentation to Markdown, one text per slide in
+/// presentation order. Each starts with a "Slide N: title" heading, followed
+/// by the slide's text and tables and then its speaker notes.
+pub fn pptx_slides(path: &Path) -> Result<Vec<String>> {
+    let mut archive = open_archive(path)?;
+    let presentation = read_part(&mut archive, "ppt/presentation.xml")?
+        .ok_or_else(|| AppError::File("Not a PowerPoint file: ppt/presentation.xml is missing".to_string()))?;
+    let relationships = read_relationships(&mut archive, "ppt/presentation.xml")?;
+
+    let mut slides = Vec::new();
+    for (index, id) in slide_ids(&presentation)?.iter().enumerate() {
+        let Some(slide_path) = relationships.get(id).map(|r| r.target.clone()) else {
+            continue;
+        };
+        let Some(xml) = read_part(&mut archive, &slide_path)? else {
+            continue;
+        };
+        let slide = drawing_text(&xml)?;
+
+        let mut text = match slide.title {
+            Some(title) => format!("# Slide {}: {}", index + 1, title),
+            None => format!("# Slide {}", index + 1),
+        };
+        if !slide.body.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&slide.body);
+        }
+
+        let notes_path = read_relationships(&mut archive, &slide_path)?
+            .into_values()
+            .find(|r| r.kind.ends_with("/notesSlide"))
+            .map(|r| r.target);
+        if let Some(xml) = notes_path.map(|p| read_part(&mut archive, &p)).transpose()?.flatten() {
+            let notes = drawing_text(&xml)?.body;
+            if !notes.is_empty() {
+                text.push_str("\n\n## Speaker notes\n\n");
+                text.push_str(&notes);
+            }
+        }
+
+        slides.push(text);
+    }
+
+    Ok(slides)
+}
+
+/// Relationship IDs of the slides, in presentation order
+fn slide_ids(presentation: &str) -> Result<Vec<String>> {
+    let mut reader = Reader::from_str(presentation);
+    let mut ids = Vec::new();
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"sldId" => {
+                ids.extend(attribute(&e, b"r:id"));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Text on a slide or notes page
+struct DrawingText {
+    /// Text of the title placeholder, if there is one
+    title: Option<String>,
+    /// Everything else as Markdown
+    body: String,
+}
+
+fn drawing_text(xml: &str) -> Result<DrawingText> {
+    let mut reader = Reader::from_str(xml);
+    let mut builder = TextBuilder::default();
+    let mut title: Vec<String> = Vec::new();
+    // Placeholder type of the current shape ("" for a body placeholder)
+    let mut placeholder: Option<String> = None;
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"sp" | b"graphicFrame" => placeholder = None,
+                b"ph" => placeholder = Some(attribute(&e, b"type").unwrap_or_default()),
+                b"p" => builder.start_paragraph(),
+                b"t" => in_text = true,
+                b"tbl" => builder.start_table(),
+                b"tr" => builder.start_row(),
+                b"tc" => builder.start_cell(),
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"ph" => placeholder = Some(attribute(&e, b"type").unwrap_or_default()),
+                b"br" => builder.text(" "),
+                _ => {}
+            },
+            Event::Text(e) if in_text => builder.text(&e.unescape().map_err(xml_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"p" => match placeholder.as_deref() {
+                    Some("title" | "ctrTitle") => title.extend(builder.close_paragraph().map(|p| p.text)),
+                    Some(kind) if SKIPPED_PLACEHOLDERS.contains(&kind) => {
+                        builder.close_paragraph();
+                    }
+                    _ => builder.end_paragraph(),
+                },
+                b"t" => in_text = false,
+                b"tbl" => builder.end_table(),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(DrawingText {
+        title: (!title.is_empty()).then(|| title.join(" ")),
+        body: builder.finish(),
+    })
+}
+
+/// Convert a workbook to Markdown, sheet by sheet: each sheet's name as a
+/// heading followed by its cells as a table, the first row being the header.
+/// Empty sheets are left out.
+pub fn xlsx_to_markdown(path: &Path) -> Result<String> {
+    let mut workbook: Xlsx<_> = open_workbook(path)
+        .map_err(|e| AppError::File(format!("Failed to open workbook: {}", e)))?;
+    let mut markdown = MarkdownWriter::new();
+
+    for name in workbook.sheet_names() {
+        let range = workbook.worksheet_range(&name)
+            .map_err(|e| AppError::File(format!("Failed to read sheet {}: {}", name, e)))?;
+        let rows: Vec<Vec<String>> = range.rows().map(|row| row.iter().map(cell_text).collect()).collect();
+        if rows.iter().flatten().all(|cell| cell.is_empty()) {
+            continue;
+        }
+
+        markdown.heading(1, &name);
+        markdown.table(&rows);
+    }
+
+    Ok(markdown.finish())
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) | Data::DateTimeIso(_) => match cell.as_datetime() {
+            Some(datetime) if datetime.time() == chrono::NaiveTime::MIN => datetime.format("%Y-%m-%d").to_string(),
+            Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => cell.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+/// Collects paragraphs and tables, in document order, into Markdown.
+/// Paragraphs inside a table cell become the cell's text, and a table
+/// nested in a cell is flattened into it.
+#[derive(Default)]
+struct TextBuilder {
+    markdown: MarkdownWriter,
+    /// Open paragraphs; text boxes and footnotes nest them
+    paragraphs: Vec<Paragraph>,
+    /// Open tables, as rows of cells
+    tables: Vec<Vec<Vec<String>>>,
+}
+
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    heading: Option<usize>,
+    list_depth: Option<usize>,
+    /// Number of a numbered list item; bulleted items have none
+    list_number: Option<usize>,
+    /// Whether the paragraph is a further paragraph of the list item before
+    /// it rather than a new item
+    continues_item: bool,
+}
+
+impl TextBuilder {
+    fn start_paragraph(&mut self) {
+        self.paragraphs.push(Paragraph::default());
+    }
+
+    fn set_heading(&mut self, level: usize) {
+        if let Some(paragraph) = self.paragraphs.last_mut() {
+            paragraph.heading = Some(level);
+        }
+    }
+
+    fn set_list_depth(&mut self, depth: Option<usize>) {
+        if let Some(paragraph) = self.paragraphs.last_mut() {
+            paragraph.list_depth = depth;
+        }
+    }
+
+    fn set_list_number(&mut self, number: Option<usize>) {
+        if let Some(paragraph) = self.paragraphs.last_mut() {
+            paragraph.list_number = number;
+        }
+    }
+
+    fn set_continues_item(&mut self) {
+        if let Some(paragraph) = self.paragraphs.last_mut() {
+            paragraph.continues_item = true;
+        }
+    }
+
+    /// Text outside any paragraph is ignored
+    fn text(&mut self, text: &str) {
+        if let Some(paragraph) = self.paragraphs.last_mut() {
+            paragraph.text.push_str(text);
+        }
+    }
+
+    /// Close the innermost paragraph and return it with its whitespace
+    /// collapsed, unless it is empty
+    fn close_paragraph(&mut self) -> Option<Paragraph> {
+        let mut paragraph = self.paragraphs.pop()?;
+        paragraph.text = paragraph.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!paragraph.text.is_empty()).then_some(paragraph)
+    }
+
+    fn end_paragraph(&mut self) {
+        let Some(paragraph) = self.close_paragraph() else {
+            return;
+        };
+
+        if let Some(cell) = self.cell() {
+            append_text(cell, &paragraph.text);
+        } else if let Some(level) = paragraph.heading {
+            self.markdown.heading(level, &paragraph.text);
+        } else if let Some(depth) = paragraph.list_depth {
+            if paragraph.continues_item {
+                self.markdown.list_continuation(depth, &paragraph.text);
+            } else {
+                let marker = paragraph.list_number.map_or(ListMarker::Bullet, ListMarker::Number);
+                self.markdown.list_item(depth, marker, &paragraph.text);
+            }
+        } else {
+            self.markdown.paragraph(&paragraph.text);
+        }
+    }
+
+    fn start_table(&mut self) {
+        self.tables.push(Vec::new());
+    }
+
+    fn start_row(&mut self) {
+        if let Some(table) = self.tables.last_mut() {
+            table.push(Vec::new());
+        }
+    }
+
+    fn start_cell(&mut self) {
+        if let Some(row) = self.tables.last_mut().and_then(|table| table.last_mut()) {
+            row.push(String::new());
+        }
+    }
+
+    fn end_table(&mut self) {
+        let Some(rows) = self.tables.pop() else {
+            return;
+        };
+
+        match self.cell() {
+            Some(cell) => {
+                for row in rows {
+                    append_text(cell, &row.join(" "));
+                }
+            }
+            None => self.markdown.table(&rows),
+        }
+    }
+
+    /// The innermost open table cell
+    fn cell(&mut self) -> Option<&mut String> {
+        self.tables.last_mut()?.last_mut()?.last_mut()
+    }
+
+    fn finish(self) -> String {
+        self.markdown.finish()
+    }
+}
+
+fn append_text(target: &mut String, text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    if !target.is_empty() {
+        target.push(' ');
+    }
+    target.push_str(text);
+}
+
+/// A relationship from one part of an Office file to another
+struct Relationship {
+    /// Relationship type URI
+    kind: String,
+    /// Path of the target part within the archive
+    target: String,
+}
+
+/// Relationships of `part`, by ID. External targets (such as hyperlinks)
+/// are left out.
+fn read_relationships(archive: &mut ZipArchive<File>, part: &str) -> Result<HashMap<String, Relationship>> {
+    let (directory, name) = part.rsplit_once('/').unwrap_or(("", part));
+    let path = format!("{}/_rels/{}.rels", directory, name);
+    let Some(xml) = read_part(archive, path.trim_start_matches('/'))? else {
+        return Ok(HashMap::new());
+    };
+
+    let mut reader = Reader::from_str(&xml);
+    let mut relationships = HashMap::new();
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"Relationship" => {
+                if attribute(&e, b"TargetMode").as_deref() == Some("External") {
+                    continue;
+                }
+                if let (Some(id), Some(kind), Some(target)) = (attribute(&e, b"Id"), attribute(&e, b"Type"), attribute(&e, b"Target")) {
+                    relationships.insert(id, Relationship { kind, target: resolve_target(directory, &target) });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(relationships)
+}
+
+/// Archive path of a relationship target given relative to `directory`
+pub(crate) fn resolve_target(directory: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+
+    let mut segments: Vec<&str> = directory.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+pub(crate) fn open_archive(path: &Path) -> Result<ZipArchive<File>> {
+    ZipArchive::new(File::open(path)?).map_err(|e| AppError::File(format!("Failed to open document: {}", e)))
+}
+
+/// The text of a part of the archive, or `None` if it has no such part
+pub(crate) fn read_part(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<String>> {
+    let part = match archive.by_name(name) {
+        Ok(part) => part,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(AppError::File(format!("Failed to read {}: {}", name, e))),
+    };
+    if part.size() > MAX_PART_BYTES {
+        return Err(AppError::File(format!("Document part {} is too large to extract", name)));
+    }
+
+    let mut text = String::new();
+    part.take(MAX_PART_BYTES).read_to_string(&mut text)?;
+    Ok(Some(text))
+}
+
+/// Value of the attribute with the (prefixed) name `name`
+pub(crate) fn attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    let attribute = e.try_get_attribute(name).ok()??;
+    let value = std::str::from_utf8(&attribute.value).ok()?;
+    quick_xml::escape::unescape(value).ok().map(|value| value.into_owned())
+}
+
+pub(crate) fn xml_error(e: quick_xml::Error) -> AppError {
+    AppError::File(format!("Failed to parse document XML: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn write_archive(name: &str, parts: &[(&str, &str)]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("omnirecall-{}-{}", uuid::Uuid::new_v4(), name));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in parts {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_docx_headings_lists_and_tables() {
+        let styles = r#"<w:styles xmlns:w="w"><w:style w:styleId="Berschrift1"><w:name w:val="heading 1"/></w:style></w:styles>"#;
+        let document = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Berschrift1"/></w:pPr><w:r><w:t>Pricing</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Plans are billed </w:t></w:r><w:r><w:t>monthly &amp; yearly.</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="1"/><w:numId w:val="3"/></w:numPr></w:pPr><w:r><w:t>Team plan</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="5"/></w:numPr></w:pPr><w:r><w:t>Sign up</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="1"/><w:numId w:val="5"/></w:numPr></w:pPr><w:r><w:t>Use SSO</w:t></w:r></w:p>
+            <w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="5"/></w:numPr></w:pPr><w:r><w:t>Pay</w:t></w:r></w:p>
+            <w:tbl>
+                <w:tr><w:tc><w:p><w:r><w:t>Plan</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Price</w:t></w:r></w:p></w:tc></w:tr>
+                <w:tr><w:tc><w:p><w:r><w:t>Team</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>$10</w:t></w:r></w:p></w:tc></w:tr>
+            </w:tbl>
+            <w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Refunds</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let numbering = r#"<w:numbering xmlns:w="w">
+            <w:abstractNum w:abstractNumId="0">
+                <w:lvl w:ilvl="0"><w:start w:val="4"/><w:numFmt w:val="decimal"/></w:lvl>
+                <w:lvl w:ilvl="1"><w:start w:val="1"/><w:numFmt w:val="bullet"/></w:lvl>
+            </w:abstractNum>
+            <w:num w:numId="5"><w:abstractNumId w:val="0"/></w:num>
+        </w:numbering>"#;
+        let path = write_archive("test.docx", &[
+            ("word/styles.xml", styles),
+            ("word/numbering.xml", numbering),
+            ("word/document.xml", document),
+        ]);
+
+        let markdown = docx_to_markdown(&path).unwrap();
+        assert_eq!(
+            markdown,
+            "# Pricing\n\nPlans are billed monthly & yearly.\n\n  - Team plan\n4. Sign up\n   - Use SSO\n5. Pay\n\n\
+             | Plan | Price |\n| --- | --- |\n| Team | $10 |\n\n## Refunds"
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_pptx_slides_in_order_with_notes() {
+        let presentation = r#"<p:presentation xmlns:p="p" xmlns:r="r"><p:sldIdLst>
+            <p:sldId id="256" r:id="rId3"/><p:sldId id="257" r:id="rId2"/>
+        </p:sldIdLst></p:presentation>"#;
+        let relationships = r#"<Relationships>
+            <Relationship Id="rId2" Type="http://schemas/slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId3" Type="http://schemas/slide" Target="slides/slide2.xml"/>
+        </Relationships>"#;
+        let slide = |title: &str, body: &str| format!(
+            r#"<p:sld xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>{}</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>{}</a:t></a:r></a:p></p:txBody></p:sp>
+                <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldNum"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>7</a:t></a:r></a:p></p:txBody></p:sp>
+            </p:spTree></p:cSld></p:sld>"#,
+            title, body
+        );
+        let (agenda, results) = (slide("Agenda", "Goals for Q3"), slide("Results", "Revenue grew"));
+        let slide_relationships = r#"<Relationships>
+            <Relationship Id="rId1" Type="http://schemas/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+        </Relationships>"#;
+        let notes = r#"<p:notes xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Mention the churn dip.</a:t></a:r></a:p></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:notes>"#;
+        let path = write_archive("test.pptx", &[
+            ("ppt/presentation.xml", presentation),
+            ("ppt/_rels/presentation.xml.rels", relationships),
+            ("ppt/slides/slide1.xml", &results),
+            ("ppt/slides/slide2.xml", &agenda),
+            ("ppt/slides/_rels/slide1.xml.rels", slide_relationships),
+            ("ppt/notesSlides/notesSlide1.xml", notes),
+        ]);
+
+        let slides = pptx_slides(&path).unwrap();
+        assert_eq!(slides, vec![
+            "# Slide 1: Agenda\n\nGoals for Q3",
+            "# Slide 2: Results\n\nRevenue grew\n\n## Speaker notes\n\nMention the churn dip.",
+        ]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_xlsx_sheets_become_tables() {
+        let workbook = r#"<workbook xmlns="main" xmlns:r="r"><sheets>
+            <sheet name="Budget" sheetId="1" r:id="rId1"/><sheet name="Empty" sheetId="2" r:id="rId2"/>
+        </sheets></workbook>"#;
+        let relationships = r#"<Relationships>
+            <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/>
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/>
+        </Relationships>"#;
+        let budget = r#"<worksheet xmlns="main"><sheetData>
+            <row r="1"><c r="A1" t="inlineStr"><is><t>Item</t></is></c><c r="B1" t="inlineStr"><is><t>Cost</t></is></c></row>
+            <row r="2"><c r="A2" t="inlineStr"><is><t>Laptops</t></is></c><c r="B2"><v>1200.5</v></c></row>
+        </sheetData></worksheet>"#;
+        let empty = r#"<worksheet xmlns="main"><sheetData/></worksheet>"#;
+        let path = write_archive("test.xlsx", &[
+            ("xl/workbook.xml", workbook),
+            ("xl/_rels/workbook.xml.rels", relationships),
+            ("xl/worksheets/sheet1.xml", budget),
+            ("xl/worksheets/sheet2.xml", empty),
+        ]);
+
+        assert_eq!(
+            xlsx_to_markdown(&path).unwrap(),
+            "# Budget\n\n| Item | Cost |\n| --- | --- |\n| Laptops | 1200.5 |"
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_odt_headings_lists_and_tables() {
+        let content = r#"<office:document-content xmlns:office="o" xmlns:style="s" xmlns:text="t" xmlns:table="tb">
+            <office:automatic-styles><text:list-style style:name="L1">
+                <text:list-level-style-number text:level="1"/><text:list-level-style-bullet text:level="2"/>
+            </text:list-style></office:automatic-styles>
+            <office:body><office:text>
+            <text:h text:outline-level="2">Setup</text:h>
+            <text:p>Install<text:s/>the<text:tab/>app.<office:annotation><text:p>Reviewer note</text:p></office:annotation></text:p>
+            <text:list text:style-name="L1"><text:list-item><text:p>Download</text:p>
+                <text:list><text:list-item><text:p>Verify checksum</text:p></text:list-item></text:list>
+            </text:list-item><text:list-item><text:p>Install</text:p><text:p>Needs admin rights.</text:p></text:list-item></text:list>
+            <table:table><table:table-row><table:table-cell><text:p>OS</text:p></table:table-cell><table:table-cell/></table:table-row>
+                <table:table-row><table:table-cell><text:p>Linux</text:p></table:table-cell><table:table-cell><text:p>apt</text:p></table:table-cell></table:table-row></table:table>
+        </office:text></office:body></office:document-content>"#;
+        let path = write_archive("test.odt", &[("content.xml", content)]);
+
+        assert_eq!(
+            odt_to_markdown(&path).unwrap(),
+            "## Setup\n\nInstall the app.\n\n1. Download\n   - Verify checksum\n2. Install\n\n   Needs admin rights.\n\n| OS |  |\n| --- | --- |\n| Linux | apt |"
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,179 @@
+/// How chunk embeddings are stored for search.
+///
+/// Vectors are always L2-normalized before storage, so relevance is a plain
+/// dot product whatever the format. The `embedding` column that every
+/// search scans holds:
+///
+/// - `None`: raw little-endian `f32`, 4 bytes per dimension.
+/// - `Int8`: one signed byte per dimension plus a 4-byte scale, ~4x smaller.
+/// - `Binary`: one sign bit per dimension, ~32x smaller, compared by
+///   Hamming distance.
+///
+/// Quantized chunks also keep their full-precision vector in the
+/// `chunk_embeddings` table. The quantized scan only shortlists candidates;
+/// the best of them are rescored against those `f32` vectors, which nothing
+/// else reads. So quantization shrinks the data each search scans, not the
+/// database file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quantization {
+    #[default]
+    None,
+    Int8,
+    Binary,
+}
+
+impl Quantization {
+    /// Parse an `embedding_quantization` config value or `embedding_format`
+    /// column value; anything unrecognised means full precision.
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "int8" => Quantization::Int8,
+            "binary" => Quantization::Binary,
+            _ => Quantization::None,
+        }
+    }
+
+    /// Value of the `embedding_format` column for vectors in this format
+    pub fn as_format(self) -> &'static str {
+        match self {
+            Quantization::None => "f32",
+            Quantization::Int8 => "int8",
+            Quantization::Binary => "binary",
+        }
+    }
+}
+
+/// Scale a vector to unit length in place (zero vectors are left alone)
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Dot product; equals cosine similarity for normalized vectors
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Encode a normalized vector as an `embedding` blob in the given format
+pub fn encode(vector: &[f32], quantization: Quantization) -> Vec<u8> {
+    match quantization {
+        Quantization::None => to_bytes(vector),
+        Quantization::Int8 => encode_int8(vector),
+        Quantization::Binary => sign_bits(vector),
+    }
+}
+
+/// Estimate the similarity of a normalized query to an `embedding` blob
+/// written by `encode`. Exact for full-precision blobs. `query_bits` are the
+/// query's `sign_bits`, used for binary blobs.
+pub fn similarity(query: &[f32], query_bits: &[u8], stored: &[u8], quantization: Quantization) -> f32 {
+    match quantization {
+        Quantization::None => dot(query, &from_bytes(stored)),
+        Quantization::Int8 => dot(query, &decode_int8(stored)),
+        Quantization::Binary => binary_similarity(query_bits, stored, query.len()),
+    }
+}
+
+/// Full-precision vector as little-endian `f32` bytes
+pub fn to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+/// Inverse of `to_bytes`
+pub fn from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks(4)
+        .map(|chunk| {
+            let arr: [u8; 4] = chunk.try_into().unwrap_or([0, 0, 0, 0]);
+            f32::from_le_bytes(arr)
+        })
+        .collect()
+}
+
+/// Pack the sign of each dimension into bits (1 = positive)
+pub fn sign_bits(vector: &[f32]) -> Vec<u8> {
+    vector.chunks(8)
+        .map(|group| {
+            group.iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, x)| if *x > 0.0 { byte | (1 << i) } else { byte })
+        })
+        .collect()
+}
+
+/// Estimate the cosine similarity of two vectors from their sign bits.
+/// For high-dimensional embeddings the fraction of differing signs is
+/// roughly `angle / pi`.
+fn binary_similarity(a: &[u8], b: &[u8], dimensions: usize) -> f32 {
+    if a.len() != b.len() || dimensions == 0 {
+        return 0.0;
+    }
+    let differing: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+    (std::f32::consts::PI * differing as f32 / dimensions as f32).cos()
+}
+
+// Layout: little-endian f32 scale, then one i8 per dimension
+fn encode_int8(vector: &[f32]) -> Vec<u8> {
+    let max = vector.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+
+    let mut bytes = Vec::with_capacity(4 + vector.len());
+    bytes.extend_from_slice(&scale.to_le_bytes());
+    bytes.extend(vector.iter().map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8 as u8));
+    bytes
+}
+
+fn decode_int8(bytes: &[u8]) -> Vec<f32> {
+    if bytes.len() < 4 {
+        return Vec::new();
+    }
+    let scale = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    bytes[4..].iter().map(|b| *b as i8 as f32 * scale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(vector: &[f32]) -> Vec<f32> {
+        let mut vector = vector.to_vec();
+        normalize(&mut vector);
+        vector
+    }
+
+    #[test]
+    fn test_int8_round_trip_preserves_similarity() {
+        let a = unit(&[0.12, -0.5, 0.33, 0.9, -0.01, 0.25, 0.0, -0.7]);
+        let b = unit(&[0.1, -0.4, 0.3, 0.8, 0.05, 0.2, -0.1, -0.6]);
+        let a_bits = sign_bits(&a);
+
+        let bytes = encode(&b, Quantization::Int8);
+        assert_eq!(bytes.len(), 4 + b.len());
+        assert!((similarity(&a, &a_bits, &bytes, Quantization::Int8) - dot(&a, &b)).abs() < 0.01);
+
+        let bytes = encode(&b, Quantization::None);
+        assert_eq!(from_bytes(&bytes), b);
+        assert_eq!(similarity(&a, &a_bits, &bytes, Quantization::None), dot(&a, &b));
+    }
+
+    #[test]
+    fn test_binary_similarity_orders_by_angle() {
+        let query = unit(&[1.0, 1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0]);
+        let close = unit(&[1.0, 0.8, -1.0, 1.0, -1.0, 1.0, 1.0, 0.1, 1.0]);
+        let far = unit(&[-1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0]);
+
+        let close_bits = encode(&close, Quantization::Binary);
+        let far_bits = encode(&far, Quantization::Binary);
+        let query_bits = sign_bits(&query);
+        assert_eq!(query_bits.len(), 2);
+        assert_eq!(close_bits.len(), 2);
+
+        let score = |bits: &[u8]| similarity(&query, &query_bits, bits, Quantization::Binary);
+        assert!(score(&close_bits) > score(&far_bits));
+        assert!((score(&query_bits) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use crate::error::Result;
+use crate::services::ai_client::AiClient;
+use crate::services::vector_store::{SearchResult, RRF_K};
+
+/// Expansions kept by `QueryExpansionCache` before the oldest are evicted
+const CACHE_CAPACITY: usize = 32;
+
+/// How a search query is rewritten before retrieval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionMode {
+    None,
+    /// Several paraphrases of the question
+    MultiQuery,
+    /// A hypothetical answer passage (HyDE), which tends to embed closer to
+    /// real answers than a short question does
+    Hyde,
+    /// Paraphrases and a hypothetical answer
+    Both,
+}
+
+impl ExpansionMode {
+    /// Parse a `query_expansion` value: "none", "multi_query", "hyde" or "both"
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "multi_query" => ExpansionMode::MultiQuery,
+            "hyde" => ExpansionMode::Hyde,
+            "both" => ExpansionMode::Both,
+            _ => ExpansionMode::None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ExpansionMode::None => "none",
+            ExpansionMode::MultiQuery => "multi_query",
+            ExpansionMode::Hyde => "hyde",
+            ExpansionMode::Both => "both",
+        }
+    }
+}
+
+/// A query text to retrieve with, and its embedding
+#[derive(Debug, Clone)]
+pub struct ExpandedQuery {
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Rewrites queries with a chat model
+pub struct QueryExpander {
+    client: AiClient,
+    model: String,
+}
+
+impl QueryExpander {
+    pub fn new(provider: &str, api_key: &str, model: &str) -> Self {
+        Self {
+            client: AiClient::new(provider, api_key, None),
+            model: model.to_string(),
+        }
+    }
+
+    /// Extra query texts for `mode` (not including the original query)
+    pub async fn expand(&self, query: &str, mode: ExpansionMode, paraphrases: usize) -> Result<Vec<String>> {
+        let mut texts = Vec::new();
+
+        if matches!(mode, ExpansionMode::MultiQuery | ExpansionMode::Both) && paraphrases > 0 {
+            let prompt = format!(
+                "Rewrite the following search query in {} different ways that could match relevant documents. \
+                 Use different wording and spell out abbreviations. \
+                 Reply with one rewrite per line and nothing else.\n\nQuery: {}",
+                paraphrases, query
+            );
+            let reply = self.client.chat_with_history(&self.model, &prompt, &[], None).await?;
+            texts.extend(parse_paraphrases(&reply, query, paraphrases));
+        }
+
+        if matches!(mode, ExpansionMode::Hyde | ExpansionMode::Both) {
+            let prompt = format!(
+                "Write a short passage (3-5 sentences) that would answer the question below, \
+                 in the style of the documentation it would appear in. \
+                 Reply with the passage only.\n\nQuestion: {}",
+                query
+            );
+            let reply = self.client.chat_with_history(&self.model, &prompt, &[], None).await?;
+            let passage = reply.trim();
+            if !passage.is_empty() {
+                texts.push(passage.to_string());
+            }
+        }
+
+        Ok(texts)
+    }
+}
+
+/// Expanded queries keyed by conversation turn, so that regenerating an
+/// answer or building context twice for the same turn doesn't pay for the
+/// rewrite and embeddings again. Kept in Tauri state.
+#[derive(Default)]
+pub struct QueryExpansionCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    queries: HashMap<String, Vec<ExpandedQuery>>,
+    /// Keys in insertion order, oldest first
+    order: VecDeque<String>,
+}
+
+impl QueryExpansionCache {
+    pub fn key(turn_id: &str, query: &str, mode: ExpansionMode) -> String {
+        format!("{}\u{0}{}\u{0}{}", turn_id, mode.as_str(), query)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<ExpandedQuery>> {
+        let entries = self.entries.lock().ok()?;
+        entries.queries.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: String, queries: Vec<ExpandedQuery>) {
+        let Ok(mut entries) = self.entries.lock() else { return };
+        let entries = &mut *entries;
+
+        if entries.queries.insert(key.clone(), queries).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > CACHE_CAPACITY {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.queries.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Merge the rankings retrieved for each query variant with reciprocal-rank
+/// fusion. A chunk keeps its best cosine and keyword scores across variants.
+pub fn fuse_rankings(rankings: Vec<Vec<SearchResult>>, top_k: usize) -> Vec<SearchResult> {
+    let mut fused: HashMap<String, SearchResult> = HashMap::new();
+
+    for ranking in rankings {
+        for (rank, result) in ranking.into_iter().enumerate() {
+            let contribution = 1.0 / (RRF_K + rank as f32 + 1.0);
+            fused.entry(result.chunk.id.clone())
+                .and_modify(|existing| {
+                    existing.score = existing.score.max(result.score);
+                    if result.keyword_score > existing.keyword_score {
+                        existing.keyword_score = result.keyword_score;
+                    }
+                    existing.fusion_score = Some(existing.fusion_score.unwrap_or(0.0) + contribution);
+                })
+                .or_insert(SearchResult {
+                    fusion_score: Some(contribution),
+                    ..result
+                });
+        }
+    }
+
+    let mut results: Vec<SearchResult> = fused.into_values().collect();
+    results.sort_by(|a, b| b.fusion_score.partial_cmp(&a.fusion_score).unwrap_or(std::cmp::Ordering::Equal));
+    results.truncate(top_k);
+    results
+}
+
+/// Take up to `limit` distinct rewrites from a one-per-line reply, dropping
+/// list markers and repeats of the original query
+fn parse_paraphrases(reply: &str, query: &str, limit: usize) -> Vec<String> {
+    let mut paraphrases: Vec<String> = Vec::new();
+    let list_marker = regex::Regex::new(r"^(?:\d+[.)]|[-*•])\s+").unwrap();
+
+    for line in reply.lines() {
+        let line = list_marker.replace(line.trim(), "");
+        let line = line.trim().trim_matches('"');
+        if line.is_empty()
+            || line.eq_ignore_ascii_case(query.trim())
+            || paraphrases.iter().any(|p| p.eq_ignore_ascii_case(line))
+        {
+            continue;
+        }
+        paraphrases.push(line.to_string());
+        if paraphrases.len() == limit {
+            break;
+        }
+    }
+
+    paraphrases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::vector_store::{DocumentChunk, Metadata, DEFAULT_COLLECTION};
+
+    #[test]
+    fn test_parse_paraphrases() {
+        let reply = "1. How do I reset my password?\n2) password reset steps\n\n- \"Reset password\"\n* password reset steps\n2FA reset";
+        assert_eq!(
+            parse_paraphrases(reply, "reset password", 3),
+            vec!["How do I reset my password?", "password reset steps", "2FA reset"]
+        );
+    }
+
+    #[test]
+    fn test_fuse_rankings_rewards_agreement() {
+        let result = |id: &str, score: f32| SearchResult {
+            chunk: DocumentChunk {
+                id: id.to_string(),
+                collection_id: DEFAULT_COLLECTION.to_string(),
+                document_id: "doc".to_string(),
+                document_name: "doc.md".to_string(),
+                content: id.to_string(),
+                embedding: Vec::new(),
+                chunk_index: 0,
+                token_count: 1,
+                metadata: Metadata::new(),
+                location: None,
+            },
+            score,
+            keyword_score: None,
+            fusion_score: None,
+            rerank_score: None,
+        };
+
+        let fused = fuse_rankings(vec![
+            vec![result("a", 0.9), result("b", 0.6)],
+            vec![result("b", 0.7), result("c", 0.8)],
+            vec![result("b", 0.75)],
+        ], 2);
+
+        let ids: Vec<&str> = fused.iter().map(|r| r.chunk.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_eq!(fused[0].score, 0.75);
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest() {
+        let cache = QueryExpansionCache::default();
+        let query = |text: &str| vec![ExpandedQuery { text: text.to_string(), embedding: vec![1.0] }];
+
+        for turn in 0..=CACHE_CAPACITY {
+            cache.insert(QueryExpansionCache::key(&turn.to_string(), "q", ExpansionMode::Hyde), query("q"));
+        }
+
+        assert!(cache.get(&QueryExpansionCache::key("0", "q", ExpansionMode::Hyde)).is_none());
+        assert!(cache.get(&QueryExpansionCache::key("1", "q", ExpansionMode::Hyde)).is_some());
+        assert!(cache.get(&QueryExpansionCache::key("1", "q", ExpansionMode::MultiQuery)).is_none());
+    }
+}
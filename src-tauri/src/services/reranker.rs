@@ -0,0 +1,166 @@
+use std::time::Duration;
+use reqwest::Client;
+use serde::Deserialize;
+use crate::error::{AppError, Result};
+use crate::services::ai_client::AiClient;
+use crate::services::vector_store::SearchResult;
+
+/// Default endpoint of a local cross-encoder server (e.g. Hugging Face
+/// text-embeddings-inference running `BAAI/bge-reranker-base`)
+pub const DEFAULT_CROSS_ENCODER_URL: &str = "http://localhost:8080";
+
+const RERANK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Longest passage excerpt sent to the LLM scorer, in characters
+const LLM_PASSAGE_CHARS: usize = 1500;
+
+/// Second-stage scorer that re-orders the top retrieval candidates by
+/// reading the query and passage together, which ranks far better than
+/// comparing two independently computed embeddings.
+pub enum Reranker {
+    /// A cross-encoder served over HTTP with a text-embeddings-inference
+    /// compatible `/rerank` endpoint
+    CrossEncoder { url: String, client: Client },
+    /// Ask a chat model to grade each passage's relevance
+    Llm { client: AiClient, model: String },
+}
+
+impl Reranker {
+    /// Build the re-ranker named by the `reranker` config value
+    /// (`"cross_encoder"` or `"llm"`); anything else disables re-ranking.
+    pub fn from_config(
+        kind: &str,
+        url: &str,
+        provider: &str,
+        api_key: &str,
+        model: &str,
+    ) -> Option<Self> {
+        match kind {
+            "cross_encoder" => Some(Reranker::CrossEncoder {
+                url: if url.is_empty() { DEFAULT_CROSS_ENCODER_URL.to_string() } else { url.to_string() },
+                client: Client::builder()
+                    .timeout(RERANK_TIMEOUT)
+                    .build()
+                    .unwrap_or_else(|_| Client::new()),
+            }),
+            "llm" => Some(Reranker::Llm {
+                client: AiClient::new(provider, api_key, None),
+                model: model.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Score the first `top_n` results, set their `rerank_score` (0.0..=1.0)
+    /// and sort them by it. Results beyond `top_n` keep their order after
+    /// the re-ranked ones. On error `results` is left untouched.
+    pub async fn rerank(&self, query: &str, results: &mut Vec<SearchResult>, top_n: usize) -> Result<()> {
+        let top_n = top_n.min(results.len());
+        if top_n == 0 {
+            return Ok(());
+        }
+
+        let passages: Vec<&str> = results[..top_n].iter().map(|r| r.chunk.content.as_str()).collect();
+        let scores = match self {
+            Reranker::CrossEncoder { url, client } => cross_encoder_scores(client, url, query, &passages).await?,
+            Reranker::Llm { client, model } => llm_scores(client, model, query, &passages).await?,
+        };
+
+        let rest = results.split_off(top_n);
+        for (result, score) in results.iter_mut().zip(scores) {
+            result.rerank_score = Some(score);
+        }
+        results.sort_by(|a, b| b.rerank_score.partial_cmp(&a.rerank_score).unwrap_or(std::cmp::Ordering::Equal));
+        results.extend(rest);
+
+        Ok(())
+    }
+}
+
+async fn cross_encoder_scores(client: &Client, url: &str, query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+    let url = format!("{}/rerank", url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "query": query,
+        "texts": passages,
+        "raw_scores": false,
+    });
+
+    let response = client.post(&url)
+        .header("Content-Type", "application/json")
+        .json(&body)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(AppError::Api(format!("Cross-encoder error: {}", response.status())));
+    }
+
+    #[derive(Deserialize)]
+    struct RankedPassage {
+        index: usize,
+        score: f32,
+    }
+
+    let ranked: Vec<RankedPassage> = response.json().await?;
+    let mut scores = vec![0.0; passages.len()];
+    for passage in ranked {
+        if let Some(score) = scores.get_mut(passage.index) {
+            *score = passage.score;
+        }
+    }
+
+    Ok(scores)
+}
+
+async fn llm_scores(client: &AiClient, model: &str, query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+    let mut prompt = format!(
+        "Rate how well each passage answers the query, from 0 (irrelevant) to 10 (directly answers it).\n\
+         Reply with only a JSON array of {} numbers, one per passage, in order.\n\n\
+         Query: {}\n\n",
+        passages.len(),
+        query
+    );
+    for (i, passage) in passages.iter().enumerate() {
+        let excerpt: String = passage.chars().take(LLM_PASSAGE_CHARS).collect();
+        prompt.push_str(&format!("Passage {}:\n{}\n\n", i + 1, excerpt));
+    }
+
+    let reply = client.chat_with_history(model, &prompt, &[], None).await?;
+    parse_llm_scores(&reply, passages.len())
+}
+
+/// Pull the JSON array of 0-10 grades out of the model's reply (which may
+/// be wrapped in prose or a code fence) and scale it to 0.0..=1.0
+fn parse_llm_scores(reply: &str, expected: usize) -> Result<Vec<f32>> {
+    let array = reply.find('[')
+        .zip(reply.rfind(']'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &reply[start..=end])
+        .ok_or_else(|| AppError::Api("Re-ranker reply contained no scores".to_string()))?;
+
+    let grades: Vec<f32> = serde_json::from_str(array)
+        .map_err(|e| AppError::Api(format!("Failed to parse re-ranker scores: {}", e)))?;
+    if grades.len() != expected {
+        return Err(AppError::Api(format!(
+            "Re-ranker returned {} scores for {} passages",
+            grades.len(),
+            expected
+        )));
+    }
+
+    Ok(grades.into_iter().map(|g| (g / 10.0).clamp(0.0, 1.0)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_llm_scores() {
+        let reply = "Here are the scores:\n```json\n[8, 2.5, 10, 12]\n```";
+        assert_eq!(parse_llm_scores(reply, 4).unwrap(), vec![0.8, 0.25, 1.0, 1.0]);
+
+        assert!(parse_llm_scores("[1, 2]", 3).is_err());
+        assert!(parse_llm_scores("no idea", 1).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::ops::Range;
+use crate::error::Result;
+use crate::services::document_pipeline::{DocumentPipeline, TextChunk};
+use crate::services::embedding::EmbeddingService;
+use crate::services::quantization;
+
+/// Sentences on each side of a gap whose embeddings are averaged when
+/// comparing the text before and after it
+const WINDOW_SENTENCES: usize = 2;
+
+/// Gaps whose distance is at or above this percentile of all gaps in the
+/// document are topic boundaries
+const BREAKPOINT_PERCENTILE: f32 = 0.9;
+
+/// Chunk `text` by topic: embed every sentence, then cut where the
+/// sentences before a gap stop resembling the ones after it. Chunks are at
+/// least `min_tokens` long (except a short final one that can't be merged)
+/// and at most `max_tokens`.
+///
+/// Every sentence is embedded on top of the chunks themselves; the
+/// sentences go out in batches of up to 100 per request (one request per
+/// sentence with Ollama).
+pub async fn chunk_semantically(
+    pipeline: &DocumentPipeline,
+    embedding_service: &EmbeddingService,
+    text: &str,
+    min_tokens: usize,
+    max_tokens: usize,
+) -> Result<Vec<TextChunk>> {
+    let sentences = pipeline.sentence_ranges(text, max_tokens);
+    let texts: Vec<String> = sentences.iter().map(|s| text[s.clone()].to_string()).collect();
+    let embeddings = embedding_service.embed_batch(&texts).await?;
+
+    Ok(semantic_ranges(pipeline, text, &sentences, &embeddings, min_tokens, max_tokens)
+        .into_iter()
+        .map(|range| pipeline.make_chunk(text[range.clone()].to_string(), range, Vec::new()))
+        .collect())
+}
+
+/// Group consecutive sentences into chunk ranges given each sentence's
+/// embedding
+fn semantic_ranges(
+    pipeline: &DocumentPipeline,
+    text: &str,
+    sentences: &[Range<usize>],
+    embeddings: &[Vec<f32>],
+    min_tokens: usize,
+    max_tokens: usize,
+) -> Vec<Range<usize>> {
+    let tokens = |first: usize, last: usize| pipeline.estimate_tokens(&text[sentences[first].start..sentences[last].end]);
+    let distances = gap_distances(embeddings);
+    let threshold = percentile(&distances, BREAKPOINT_PERCENTILE);
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    for (gap, distance) in distances.iter().enumerate() {
+        let topic_changes = *distance >= threshold && *distance > 0.0 && tokens(start, gap) >= min_tokens;
+        if topic_changes || tokens(start, gap + 1) > max_tokens {
+            ranges.push(sentences[start].start..sentences[gap].end);
+            start = gap + 1;
+        }
+    }
+    if start < sentences.len() {
+        let last = sentences[start].start..sentences[sentences.len() - 1].end;
+        // Fold a short tail into the previous chunk if there is room
+        match ranges.last_mut() {
+            Some(previous)
+                if pipeline.estimate_tokens(&text[last.clone()]) < min_tokens
+                    && pipeline.estimate_tokens(&text[previous.start..last.end]) <= max_tokens =>
+            {
+                previous.end = last.end;
+            }
+            _ => ranges.push(last),
+        }
+    }
+
+    ranges
+}
+
+/// Cosine distance across each gap between consecutive sentences, comparing
+/// the mean embedding of the window before the gap with the one after it
+fn gap_distances(embeddings: &[Vec<f32>]) -> Vec<f32> {
+    let window_mean = |window: &[Vec<f32>]| {
+        let mut mean = vec![0.0; window.first().map_or(0, Vec::len)];
+        for embedding in window {
+            let mut embedding = embedding.clone();
+            quantization::normalize(&mut embedding);
+            for (m, e) in mean.iter_mut().zip(embedding) {
+                *m += e;
+            }
+        }
+        quantization::normalize(&mut mean);
+        mean
+    };
+
+    (1..embeddings.len())
+        .map(|after| {
+            let before = &embeddings[after.saturating_sub(WINDOW_SENTENCES)..after];
+            let following = &embeddings[after..(after + WINDOW_SENTENCES).min(embeddings.len())];
+            1.0 - quantization::dot(&window_mean(before), &window_mean(following))
+        })
+        .collect()
+}
+
+/// The value at `fraction` (0.0..=1.0) through the sorted values, or
+/// infinity if there are none
+fn percentile(values: &[f32], fraction: f32) -> f32 {
+    if values.is_empty() {
+        return f32::INFINITY;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sorted[((sorted.len() - 1) as f32 * fraction).round() as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splits_where_topic_changes() {
+        let pipeline = DocumentPipeline::new();
+        let text = "Cats purr. Cats nap. Cats hunt mice. Rust compiles. Rust borrows. Rust is fast.";
+        let sentences = pipeline.sentence_ranges(text, 100);
+        let embeddings: Vec<Vec<f32>> = (0..6)
+            .map(|i| if i < 3 { vec![1.0, 0.1 * i as f32] } else { vec![0.1 * i as f32, 1.0] })
+            .collect();
+        let chunks = |min_tokens, max_tokens| -> Vec<&str> {
+            semantic_ranges(&pipeline, text, &sentences, &embeddings, min_tokens, max_tokens)
+                .into_iter()
+                .map(|r| &text[r])
+                .collect()
+        };
+
+        assert_eq!(chunks(1, 100), vec!["Cats purr. Cats nap. Cats hunt mice.", "Rust compiles. Rust borrows. Rust is fast."]);
+        // Below the minimum size the boundary is ignored...
+        assert_eq!(chunks(15, 100), vec![text]);
+        // ...and the maximum size forces one
+        assert_eq!(chunks(1, 8), vec!["Cats purr. Cats nap.", "Cats hunt mice.", "Rust compiles. Rust borrows.", "Rust is fast."]);
+    }
+}
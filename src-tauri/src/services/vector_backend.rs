@@ -0,0 +1,199 @@
+use crate::error::Result;
+use crate::services::metadata_filter::MetadataFilter;
+use crate::services::vector_store::{DocumentChunk, DocumentRecord, SearchResult, VectorStore};
+
+/// Chunk and document totals for one collection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackendStats {
+    pub document_count: i64,
+    pub chunk_count: i64,
+}
+
+/// Storage and nearest-neighbour search for chunk embeddings.
+///
+/// `VectorStore` (SQLite) is the default implementation. Other backends,
+/// e.g. a local Qdrant instance or a LanceDB directory for very large
+/// corpora, implement this trait and must pass `conformance::run`.
+///
+/// Embeddings handed to `store_document` need not be normalized; scores
+/// returned by `search` are cosine similarities, highest first.
+pub trait VectorBackend: Send + Sync {
+    /// Replace everything stored for `document` with `chunks`. Readers must
+    /// see either the old or the new chunk set, never a mix.
+    fn store_document(&self, document: &DocumentRecord, chunks: &[DocumentChunk]) -> Result<()>;
+
+    /// Remove a document and its chunks. Removing an unknown document is not
+    /// an error.
+    fn remove_document(&self, collection_id: &str, document_id: &str) -> Result<()>;
+
+    /// The `top_k` chunks in a collection most similar to the query,
+    /// considering only chunks that match `filter`
+    fn search(
+        &self,
+        collection_id: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>>;
+
+    fn stats(&self, collection_id: &str) -> Result<BackendStats>;
+}
+
+impl VectorBackend for VectorStore {
+    fn store_document(&self, document: &DocumentRecord, chunks: &[DocumentChunk]) -> Result<()> {
+        self.store_chunks(document, chunks)
+    }
+
+    fn remove_document(&self, collection_id: &str, document_id: &str) -> Result<()> {
+        VectorStore::remove_document(self, collection_id, document_id)
+    }
+
+    fn search(
+        &self,
+        collection_id: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        VectorStore::search(self, collection_id, query_embedding, top_k, filter)
+    }
+
+    fn stats(&self, collection_id: &str) -> Result<BackendStats> {
+        Ok(BackendStats {
+            document_count: self.get_document_count(collection_id)?,
+            chunk_count: self.get_chunk_count(collection_id)?,
+        })
+    }
+}
+
+/// Behaviour every `VectorBackend` must provide. Call `run` from a test in
+/// the backend's module with a factory for empty instances.
+#[cfg(test)]
+pub mod conformance {
+    use super::*;
+    use crate::services::vector_store::{IndexStatus, Metadata, DEFAULT_COLLECTION};
+
+    /// Run every conformance check, each against a fresh backend
+    pub fn run<B: VectorBackend>(new_backend: impl Fn() -> B) {
+        empty_collection(&new_backend());
+        ranks_by_similarity(&new_backend());
+        store_replaces_document(&new_backend());
+        remove_document(&new_backend());
+        collections_are_isolated(&new_backend());
+        applies_metadata_filter(&new_backend());
+    }
+
+    fn document(id: &str, collection_id: &str, chunk_count: usize) -> DocumentRecord {
+        DocumentRecord {
+            id: id.to_string(),
+            collection_id: collection_id.to_string(),
+            name: format!("{}.md", id),
+            path: format!("/docs/{}.md", id),
+            size: 100,
+            content_hash: String::new(),
+            mtime: None,
+            chunk_count: chunk_count as i64,
+            embedding_model: "test-model".to_string(),
+            indexed_at: None,
+            status: IndexStatus::Indexed,
+            last_error: None,
+            chunking_strategy: String::new(),
+        }
+    }
+
+    fn chunks(document: &DocumentRecord, embeddings: &[(&str, Vec<f32>)]) -> Vec<DocumentChunk> {
+        embeddings.iter().enumerate().map(|(i, (content, embedding))| DocumentChunk {
+            id: format!("{}-{}-{}", document.collection_id, document.id, i),
+            collection_id: document.collection_id.clone(),
+            document_id: document.id.clone(),
+            document_name: document.name.clone(),
+            content: content.to_string(),
+            embedding: embedding.clone(),
+            chunk_index: i as i32,
+            token_count: 3,
+            metadata: Metadata::new(),
+            location: None,
+        }).collect()
+    }
+
+    fn store(backend: &impl VectorBackend, id: &str, collection_id: &str, embeddings: &[(&str, Vec<f32>)]) {
+        let document = document(id, collection_id, embeddings.len());
+        backend.store_document(&document, &chunks(&document, embeddings)).unwrap();
+    }
+
+    fn contents(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.chunk.content.as_str()).collect()
+    }
+
+    fn empty_collection(backend: &impl VectorBackend) {
+        assert!(backend.search(DEFAULT_COLLECTION, &[1.0, 0.0], 5, None).unwrap().is_empty());
+        assert_eq!(backend.stats(DEFAULT_COLLECTION).unwrap(), BackendStats { document_count: 0, chunk_count: 0 });
+        backend.remove_document(DEFAULT_COLLECTION, "missing").unwrap();
+    }
+
+    fn ranks_by_similarity(backend: &impl VectorBackend) {
+        store(backend, "a", DEFAULT_COLLECTION, &[
+            ("exact", vec![2.0, 0.0]),
+            ("orthogonal", vec![0.0, 1.0]),
+            ("close", vec![1.0, 0.2]),
+        ]);
+
+        let results = backend.search(DEFAULT_COLLECTION, &[1.0, 0.0], 2, None).unwrap();
+        assert_eq!(contents(&results), vec!["exact", "close"]);
+        assert!((results[0].score - 1.0).abs() < 0.02, "scores are cosine similarities");
+        assert!(results[0].score >= results[1].score);
+        assert_eq!(results[0].chunk.document_id, "a");
+    }
+
+    fn store_replaces_document(backend: &impl VectorBackend) {
+        store(backend, "a", DEFAULT_COLLECTION, &[("old one", vec![1.0, 0.0]), ("old two", vec![0.9, 0.1])]);
+        store(backend, "a", DEFAULT_COLLECTION, &[("new", vec![1.0, 0.0])]);
+
+        let results = backend.search(DEFAULT_COLLECTION, &[1.0, 0.0], 10, None).unwrap();
+        assert_eq!(contents(&results), vec!["new"]);
+        assert_eq!(backend.stats(DEFAULT_COLLECTION).unwrap(), BackendStats { document_count: 1, chunk_count: 1 });
+    }
+
+    fn remove_document(backend: &impl VectorBackend) {
+        store(backend, "a", DEFAULT_COLLECTION, &[("keep", vec![1.0, 0.0])]);
+        store(backend, "b", DEFAULT_COLLECTION, &[("drop", vec![1.0, 0.0]), ("drop too", vec![0.0, 1.0])]);
+
+        backend.remove_document(DEFAULT_COLLECTION, "b").unwrap();
+        let results = backend.search(DEFAULT_COLLECTION, &[1.0, 0.0], 10, None).unwrap();
+        assert_eq!(contents(&results), vec!["keep"]);
+        assert_eq!(backend.stats(DEFAULT_COLLECTION).unwrap(), BackendStats { document_count: 1, chunk_count: 1 });
+    }
+
+    fn collections_are_isolated(backend: &impl VectorBackend) {
+        store(backend, "a", DEFAULT_COLLECTION, &[("default", vec![1.0, 0.0])]);
+        store(backend, "a", "other", &[("other", vec![1.0, 0.0])]);
+
+        let results = backend.search("other", &[1.0, 0.0], 10, None).unwrap();
+        assert_eq!(contents(&results), vec!["other"]);
+
+        backend.remove_document("other", "a").unwrap();
+        assert_eq!(backend.stats(DEFAULT_COLLECTION).unwrap().chunk_count, 1);
+    }
+
+    fn applies_metadata_filter(backend: &impl VectorBackend) {
+        let document = document("a", DEFAULT_COLLECTION, 2);
+        let mut chunks = chunks(&document, &[("tagged", vec![0.5, 0.5]), ("untagged", vec![1.0, 0.0])]);
+        chunks[0].metadata.insert("tags".to_string(), serde_json::json!(["legal"]));
+        backend.store_document(&document, &chunks).unwrap();
+
+        let filter = MetadataFilter::parse("tag = legal").unwrap();
+        let results = backend.search(DEFAULT_COLLECTION, &[1.0, 0.0], 10, Some(&filter)).unwrap();
+        assert_eq!(contents(&results), vec!["tagged"]);
+        assert_eq!(results[0].chunk.metadata["tags"], serde_json::json!(["legal"]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_backend_conforms() {
+        conformance::run(|| VectorStore::open_in_memory().unwrap());
+    }
+}
@@ -1,30 +1,200 @@
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, Row, Transaction, params, params_from_iter};
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
 use crate::error::{AppError, Result};
-use std::path::PathBuf;
+use crate::services::metadata_filter::MetadataFilter;
+use crate::services::migrations;
+use crate::services::quantization::{self, Quantization};
+use crate::services::vector_backend::VectorBackend;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::Duration;
 use directories::ProjectDirs;
 
+/// Rank offset used by reciprocal-rank fusion. 60 is the value from the
+/// original RRF paper and dampens the influence of the very top ranks.
+pub const RRF_K: f32 = 60.0;
+
+/// With quantized embeddings, this many candidates per requested result are
+/// kept from the quantized pre-pass and rescored at full precision
+const RESCORE_FACTOR: usize = 4;
+const MIN_RESCORE_CANDIDATES: usize = 50;
+
+/// Chunks converted per transaction by `reencode_embeddings`
+const REENCODE_BATCH: usize = 1000;
+
+/// Collection that chunks land in when the caller doesn't pick one.
+/// Created on first open and cannot be deleted.
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// Columns selected for every `DocumentChunk`, in the order `chunk_from_row`
+/// reads them. The embedding is the full-precision one, so queries must
+/// join `CHUNK_EMBEDDING_JOIN`.
+const CHUNK_COLUMNS: &str =
+    "c.id, c.collection_id, c.document_id, c.document_name, c.content,
+     CASE WHEN c.embedding_format = 'f32' THEN c.embedding ELSE e.embedding END, c.chunk_index, c.token_count, c.metadata,
+     c.char_start, c.char_end, c.line_start, c.line_end, c.page_start, c.page_end";
+
+/// Join of quantized chunks to their full-precision vectors
+const CHUNK_EMBEDDING_JOIN: &str = "LEFT JOIN chunk_embeddings e ON e.chunk_id = c.id";
+
+/// Free-form metadata attached to a chunk (file path, file type, tags,
+/// author, modified date, page number, section heading, ...)
+pub type Metadata = serde_json::Map<String, serde_json::Value>;
+
 /// A document chunk with embedding for semantic search
 #[derive(Debug, Clone)]
 pub struct DocumentChunk {
     pub id: String,
+    pub collection_id: String,
     pub document_id: String,
     pub document_name: String,
     pub content: String,
     pub embedding: Vec<f32>,
     pub chunk_index: i32,
     pub token_count: i32,
+    pub metadata: Metadata,
+    /// Where the chunk came from in its document; `None` for chunks indexed
+    /// before locations were recorded
+    pub location: Option<ChunkLocation>,
+}
+
+/// Where a chunk's text came from in its document, so that answers can cite
+/// "page 14" or "lines 30-42"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    /// Character offsets into the document's extracted text, end exclusive
+    pub char_start: usize,
+    pub char_end: usize,
+    /// First and last line, counting from 1
+    pub line_start: usize,
+    pub line_end: usize,
+    /// First and last page, counting from 1, for paginated formats such as PDF
+    pub page_start: Option<usize>,
+    pub page_end: Option<usize>,
 }
 
 /// Search result with relevance score
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub chunk: DocumentChunk,
+    /// Cosine similarity between the query and the chunk embedding
+    /// (approximate for quantized embeddings)
     pub score: f32,
+    /// BM25 relevance (higher is better), if the chunk matched the keyword query
+    pub keyword_score: Option<f32>,
+    /// Reciprocal-rank fusion score, set by hybrid search
+    pub fusion_score: Option<f32>,
+    /// Relevance from the re-ranking stage (0.0..=1.0), if it ran
+    pub rerank_score: Option<f32>,
+}
+
+/// Indexing state of a registered document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexStatus {
+    Pending,
+    Indexing,
+    Indexed,
+    Failed,
+}
+
+impl IndexStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            IndexStatus::Pending => "pending",
+            IndexStatus::Indexing => "indexing",
+            IndexStatus::Indexed => "indexed",
+            IndexStatus::Failed => "failed",
+        }
+    }
+    
+    fn parse(status: &str) -> Self {
+        match status {
+            "indexing" => IndexStatus::Indexing,
+            "indexed" => IndexStatus::Indexed,
+            "failed" => IndexStatus::Failed,
+            _ => IndexStatus::Pending,
+        }
+    }
+}
+
+/// Registry entry describing an indexed document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub id: String,
+    pub collection_id: String,
+    pub name: String,
+    pub path: String,
+    pub size: i64,
+    pub content_hash: String,
+    pub mtime: Option<String>,
+    pub chunk_count: i64,
+    pub embedding_model: String,
+    pub indexed_at: Option<String>,
+    pub status: IndexStatus,
+    pub last_error: Option<String>,
+    /// Chunking strategy that produced the stored chunks (empty for
+    /// documents indexed before strategies were recorded)
+    #[serde(default)]
+    pub chunking_strategy: String,
 }
 
-/// Local vector store using SQLite with cosine similarity
+/// A named workspace that scopes indexing and search
+#[derive(Debug, Clone, Serialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub chunk_count: i64,
+    pub created_at: String,
+}
+
+/// Local vector store using SQLite with cosine similarity.
+///
+/// The connection is guarded by a mutex so one store can be shared by all
+/// commands; see `SharedVectorStore`.
 pub struct VectorStore {
-    conn: Connection,
+    conn: Mutex<Connection>,
+    /// Format for newly stored embeddings. Existing chunks keep the format
+    /// they were written in until their document is re-indexed or
+    /// `reencode_embeddings` converts them.
+    quantization: RwLock<Quantization>,
+}
+
+/// Process-wide vector store kept in Tauri state. The database is opened on
+/// first use so that an unreadable index doesn't prevent the app starting.
+#[derive(Default)]
+pub struct SharedVectorStore {
+    store: Mutex<Option<Arc<VectorStore>>>,
+}
+
+impl SharedVectorStore {
+    /// Get the shared store, opening the database if needed. The embedding
+    /// format is re-read from the config each time, so a settings change
+    /// applies to the next document indexed.
+    pub fn get(&self) -> Result<Arc<VectorStore>> {
+        let mut store = self.store.lock()
+            .map_err(|_| AppError::Database("Vector store lock poisoned".to_string()))?;
+        
+        let opened = match store.as_ref() {
+            Some(store) => store.clone(),
+            None => {
+                let opened = Arc::new(VectorStore::new()?);
+                *store = Some(opened.clone());
+                opened
+            }
+        };
+        opened.set_quantization(Quantization::parse(&crate::config::load_config().embedding_quantization));
+        Ok(opened)
+    }
+    
+    /// The store as a generic `VectorBackend`. Chunk storage, removal, vector
+    /// search and stats go through this; collections, the document registry
+    /// and keyword search are SQLite features used via `get`.
+    pub fn backend(&self) -> Result<Arc<dyn VectorBackend>> {
+        Ok(self.get()?)
+    }
 }
 
 impl VectorStore {
@@ -40,24 +210,60 @@ impl VectorStore {
         let conn = Connection::open(&db_path)
             .map_err(|e| AppError::Database(format!("Failed to open database: {}", e)))?;
         
-        // Initialize schema
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS chunks (
-                id TEXT PRIMARY KEY,
-                document_id TEXT NOT NULL,
-                document_name TEXT NOT NULL,
-                content TEXT NOT NULL,
-                embedding BLOB NOT NULL,
-                chunk_index INTEGER NOT NULL,
-                token_count INTEGER NOT NULL,
-                created_at TEXT DEFAULT CURRENT_TIMESTAMP
-            );
-            
-            CREATE INDEX IF NOT EXISTS idx_chunks_document_id ON chunks(document_id);
-            CREATE INDEX IF NOT EXISTS idx_chunks_document_name ON chunks(document_name);"
-        ).map_err(|e| AppError::Database(format!("Failed to create schema: {}", e)))?;
+        // WAL lets searches proceed while a document is being indexed
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.busy_timeout(Duration::from_secs(5)))
+            .map_err(|e| AppError::Database(format!("Failed to configure database: {}", e)))?;
         
-        Ok(Self { conn })
+        Self::with_connection(conn, Some(&db_path))
+    }
+    
+    /// A throwaway in-memory store for tests
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| AppError::Database(format!("Failed to open database: {}", e)))?;
+        Self::with_connection(conn, None)
+    }
+    
+    fn with_connection(mut conn: Connection, db_path: Option<&Path>) -> Result<Self> {
+        migrations::migrate(&mut conn, db_path)?;
+        
+        Ok(Self { conn: Mutex::new(conn), quantization: RwLock::new(Quantization::None) })
+    }
+    
+    /// Store new embeddings in the given format
+    #[cfg(test)]
+    pub(crate) fn with_quantization(self, quantization: Quantization) -> Self {
+        self.set_quantization(quantization);
+        self
+    }
+    
+    /// Store embeddings written from now on in the given format
+    pub fn set_quantization(&self, quantization: Quantization) {
+        if let Ok(mut current) = self.quantization.write() {
+            *current = quantization;
+        }
+    }
+    
+    fn quantization(&self) -> Quantization {
+        self.quantization.read().map(|q| *q).unwrap_or_default()
+    }
+    
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn.lock()
+            .map_err(|_| AppError::Database("Vector store lock poisoned".to_string()))
+    }
+    
+    /// Run `f` inside a transaction, committing only if it succeeds
+    fn write<T>(&self, f: impl FnOnce(&Transaction) -> rusqlite::Result<T>) -> Result<T> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()
+            .map_err(|e| AppError::Database(format!("Failed to start transaction: {}", e)))?;
+        let value = f(&tx).map_err(|e| AppError::Database(e.to_string()))?;
+        tx.commit()
+            .map_err(|e| AppError::Database(format!("Failed to commit transaction: {}", e)))?;
+        Ok(value)
     }
     
     fn get_db_path() -> Result<PathBuf> {
@@ -67,64 +273,396 @@ impl VectorStore {
         Ok(proj_dirs.data_dir().join("vectors.db"))
     }
     
-    /// Store a document chunk with its embedding
-    pub fn store_chunk(&self, chunk: &DocumentChunk) -> Result<()> {
-        let embedding_bytes = Self::embedding_to_bytes(&chunk.embedding);
+    // ============= Collections =============
+    
+    /// Create a new, empty collection
+    pub fn create_collection(&self, name: &str) -> Result<Collection> {
+        let name = Self::validate_collection_name(name)?;
+        let conn = self.lock()?;
+        if Self::find_collection_by_name(&conn, name)?.is_some() {
+            return Err(AppError::Database(format!("Collection '{}' already exists", name)));
+        }
+        
+        let id = uuid::Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO collections (id, name) VALUES (?1, ?2)",
+            params![id, name],
+        ).map_err(|e| AppError::Database(format!("Failed to create collection: {}", e)))?;
+        
+        Self::get_collection(&conn, &id)
+    }
+    
+    /// Rename an existing collection
+    pub fn rename_collection(&self, collection_id: &str, name: &str) -> Result<Collection> {
+        let name = Self::validate_collection_name(name)?;
+        let conn = self.lock()?;
+        if let Some(existing) = Self::find_collection_by_name(&conn, name)? {
+            if existing != collection_id {
+                return Err(AppError::Database(format!("Collection '{}' already exists", name)));
+            }
+        }
+        
+        let updated = conn.execute(
+            "UPDATE collections SET name = ?2 WHERE id = ?1",
+            params![collection_id, name],
+        ).map_err(|e| AppError::Database(format!("Failed to rename collection: {}", e)))?;
         
-        self.conn.execute(
-            "INSERT OR REPLACE INTO chunks (id, document_id, document_name, content, embedding, chunk_index, token_count)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        if updated == 0 {
+            return Err(AppError::Database(format!("Collection not found: {}", collection_id)));
+        }
+        
+        Self::get_collection(&conn, collection_id)
+    }
+    
+    /// List all collections with their chunk counts
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT col.id, col.name, col.created_at,
+                    (SELECT COUNT(*) FROM chunks c WHERE c.collection_id = col.id)
+             FROM collections col
+             ORDER BY col.created_at, col.name"
+        ).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+        
+        let collections = stmt.query_map([], Self::collection_from_row)
+            .map_err(|e| AppError::Database(format!("Failed to query collections: {}", e)))?;
+        
+        Ok(collections.filter_map(|c| c.ok()).collect())
+    }
+    
+    /// Delete a collection and every chunk indexed into it
+    pub fn delete_collection(&self, collection_id: &str) -> Result<()> {
+        if collection_id == DEFAULT_COLLECTION {
+            return Err(AppError::Database("The default collection cannot be deleted".to_string()));
+        }
+        
+        self.write(|tx| {
+            Self::clear_collection_in(tx, collection_id)?;
+            tx.execute("DELETE FROM collections WHERE id = ?1", params![collection_id])?;
+            Ok(())
+        }).map_err(|e| AppError::Database(format!("Failed to delete collection: {}", e)))
+    }
+    
+    /// Remove every chunk in a collection, keeping the collection itself
+    pub fn clear_collection(&self, collection_id: &str) -> Result<()> {
+        self.write(|tx| Self::clear_collection_in(tx, collection_id))
+            .map_err(|e| AppError::Database(format!("Failed to clear collection: {}", e)))
+    }
+    
+    /// Remove every indexed document from every collection in one
+    /// transaction. Collections themselves are kept.
+    pub fn clear_all(&self) -> Result<()> {
+        self.write(|tx| {
+            tx.execute_batch(
+                "DELETE FROM chunks_fts;
+                 DELETE FROM chunk_embeddings;
+                 DELETE FROM chunks;
+                 DELETE FROM documents;"
+            )
+        }).map_err(|e| AppError::Database(format!("Failed to clear index: {}", e)))
+    }
+    
+    fn clear_collection_in(tx: &Transaction, collection_id: &str) -> rusqlite::Result<()> {
+        tx.execute(
+            "DELETE FROM chunks_fts WHERE chunk_id IN (SELECT id FROM chunks WHERE collection_id = ?1)",
+            params![collection_id],
+        )?;
+        tx.execute(
+            "DELETE FROM chunk_embeddings WHERE chunk_id IN (SELECT id FROM chunks WHERE collection_id = ?1)",
+            params![collection_id],
+        )?;
+        tx.execute("DELETE FROM chunks WHERE collection_id = ?1", params![collection_id])?;
+        tx.execute("DELETE FROM documents WHERE collection_id = ?1", params![collection_id])?;
+        Ok(())
+    }
+    
+    /// Fail unless the collection exists
+    pub fn ensure_collection(&self, collection_id: &str) -> Result<()> {
+        Self::get_collection(&*self.lock()?, collection_id).map(|_| ())
+    }
+    
+    fn get_collection(conn: &Connection, collection_id: &str) -> Result<Collection> {
+        conn.query_row(
+            "SELECT col.id, col.name, col.created_at,
+                    (SELECT COUNT(*) FROM chunks c WHERE c.collection_id = col.id)
+             FROM collections col WHERE col.id = ?1",
+            params![collection_id],
+            Self::collection_from_row,
+        )
+        .optional()
+        .map_err(|e| AppError::Database(format!("Failed to load collection: {}", e)))?
+        .ok_or_else(|| AppError::Database(format!("Collection not found: {}", collection_id)))
+    }
+    
+    fn find_collection_by_name(conn: &Connection, name: &str) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT id FROM collections WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| AppError::Database(format!("Failed to load collection: {}", e)))
+    }
+    
+    fn validate_collection_name(name: &str) -> Result<&str> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::Config("Collection name cannot be empty".to_string()));
+        }
+        Ok(name)
+    }
+    
+    // ============= Document registry =============
+    
+    /// Insert or update a document's registry entry
+    pub fn upsert_document(&self, document: &DocumentRecord) -> Result<()> {
+        let conn = self.lock()?;
+        Self::upsert_document_in(&conn, document)
+            .map_err(|e| AppError::Database(format!("Failed to register document: {}", e)))
+    }
+    
+    /// Record a document's indexing status without touching the rest of an
+    /// existing registry entry, so a failed re-index keeps describing the
+    /// chunks that are still stored. Unknown documents are registered as given.
+    pub fn set_document_status(&self, document: &DocumentRecord) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO documents
+                (id, collection_id, name, path, size, content_hash, mtime, chunk_count,
+                 embedding_model, indexed_at, status, last_error, chunking_strategy)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT (collection_id, id) DO UPDATE SET
+                status = excluded.status,
+                last_error = excluded.last_error",
             params![
-                chunk.id,
-                chunk.document_id,
-                chunk.document_name,
-                chunk.content,
-                embedding_bytes,
-                chunk.chunk_index,
-                chunk.token_count,
+                document.id,
+                document.collection_id,
+                document.name,
+                document.path,
+                document.size,
+                document.content_hash,
+                document.mtime,
+                document.chunk_count,
+                document.embedding_model,
+                document.indexed_at,
+                document.status.as_str(),
+                document.last_error,
+                document.chunking_strategy,
             ],
-        ).map_err(|e| AppError::Database(format!("Failed to store chunk: {}", e)))?;
+        ).map_err(|e| AppError::Database(format!("Failed to update document status: {}", e)))?;
         
         Ok(())
     }
     
-    /// Remove all chunks for a document
-    pub fn remove_document(&self, document_id: &str) -> Result<()> {
-        self.conn.execute(
-            "DELETE FROM chunks WHERE document_id = ?1",
-            params![document_id],
-        ).map_err(|e| AppError::Database(format!("Failed to remove document: {}", e)))?;
-        
+    fn upsert_document_in(conn: &Connection, document: &DocumentRecord) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO documents
+                (id, collection_id, name, path, size, content_hash, mtime, chunk_count,
+                 embedding_model, indexed_at, status, last_error, chunking_strategy)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                document.id,
+                document.collection_id,
+                document.name,
+                document.path,
+                document.size,
+                document.content_hash,
+                document.mtime,
+                document.chunk_count,
+                document.embedding_model,
+                document.indexed_at,
+                document.status.as_str(),
+                document.last_error,
+                document.chunking_strategy,
+            ],
+        )?;
         Ok(())
     }
     
-    /// Search for similar chunks using cosine similarity
-    pub fn search(&self, query_embedding: &[f32], top_k: usize) -> Result<Vec<SearchResult>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, document_id, document_name, content, embedding, chunk_index, token_count FROM chunks"
+    /// Get a document's registry entry
+    pub fn get_document(&self, collection_id: &str, document_id: &str) -> Result<Option<DocumentRecord>> {
+        let conn = self.lock()?;
+        conn.query_row(
+            "SELECT id, collection_id, name, path, size, content_hash, mtime, chunk_count,
+                    embedding_model, indexed_at, status, last_error, chunking_strategy
+             FROM documents WHERE collection_id = ?1 AND id = ?2",
+            params![collection_id, document_id],
+            Self::document_from_row,
+        )
+        .optional()
+        .map_err(|e| AppError::Database(format!("Failed to query document: {}", e)))
+    }
+    
+    /// List the registered documents in a collection
+    pub fn list_documents(&self, collection_id: &str) -> Result<Vec<DocumentRecord>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, collection_id, name, path, size, content_hash, mtime, chunk_count,
+                    embedding_model, indexed_at, status, last_error, chunking_strategy
+             FROM documents WHERE collection_id = ?1
+             ORDER BY name"
         ).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
         
-        let chunks = stmt.query_map([], |row| {
-            let embedding_bytes: Vec<u8> = row.get(4)?;
-            let embedding = Self::bytes_to_embedding(&embedding_bytes);
+        let documents = stmt.query_map(params![collection_id], Self::document_from_row)
+            .map_err(|e| AppError::Database(format!("Failed to query documents: {}", e)))?;
+        
+        Ok(documents.filter_map(|d| d.ok()).collect())
+    }
+    
+    // ============= Chunks =============
+    
+    /// Replace all of a document's chunks and its registry entry in one
+    /// transaction. Readers see either the previous chunk set or the complete
+    /// new one, never a mix, and nothing changes if any insert fails.
+    pub fn store_chunks(&self, document: &DocumentRecord, chunks: &[DocumentChunk]) -> Result<()> {
+        if let Some(chunk) = chunks.iter().find(|c| {
+            c.collection_id != document.collection_id || c.document_id != document.id
+        }) {
+            return Err(AppError::Database(format!(
+                "Chunk {} does not belong to document {}",
+                chunk.id, document.id
+            )));
+        }
+        
+        let quantization = self.quantization();
+        self.write(|tx| {
+            Self::delete_document_chunks(tx, &document.collection_id, &document.id)?;
             
-            Ok(DocumentChunk {
-                id: row.get(0)?,
-                document_id: row.get(1)?,
-                document_name: row.get(2)?,
-                content: row.get(3)?,
-                embedding,
-                chunk_index: row.get(5)?,
-                token_count: row.get(6)?,
-            })
+            let mut insert_chunk = tx.prepare(
+                "INSERT OR REPLACE INTO chunks (id, collection_id, document_id, document_name, content, embedding, chunk_index, token_count, metadata, embedding_format,
+                                             char_start, char_end, line_start, line_end, page_start, page_end)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            )?;
+            let mut delete_fts = tx.prepare("DELETE FROM chunks_fts WHERE chunk_id = ?1")?;
+            let mut insert_fts = tx.prepare("INSERT INTO chunks_fts (chunk_id, content) VALUES (?1, ?2)")?;
+            let mut delete_full = tx.prepare("DELETE FROM chunk_embeddings WHERE chunk_id = ?1")?;
+            let mut insert_full = tx.prepare("INSERT INTO chunk_embeddings (chunk_id, embedding) VALUES (?1, ?2)")?;
+            
+            for chunk in chunks {
+                let metadata = serde_json::Value::Object(chunk.metadata.clone()).to_string();
+                let mut embedding = chunk.embedding.clone();
+                quantization::normalize(&mut embedding);
+                let location = chunk.location.as_ref();
+                
+                insert_chunk.execute(params![
+                    chunk.id,
+                    chunk.collection_id,
+                    chunk.document_id,
+                    chunk.document_name,
+                    chunk.content,
+                    quantization::encode(&embedding, quantization),
+                    chunk.chunk_index,
+                    chunk.token_count,
+                    metadata,
+                    quantization.as_format(),
+                    location.map(|l| l.char_start),
+                    location.map(|l| l.char_end),
+                    location.map(|l| l.line_start),
+                    location.map(|l| l.line_end),
+                    location.and_then(|l| l.page_start),
+                    location.and_then(|l| l.page_end),
+                ])?;
+                delete_fts.execute(params![chunk.id])?;
+                insert_fts.execute(params![chunk.id, chunk.content])?;
+                delete_full.execute(params![chunk.id])?;
+                if quantization != Quantization::None {
+                    insert_full.execute(params![chunk.id, quantization::to_bytes(&embedding)])?;
+                }
+            }
+            
+            Self::upsert_document_in(tx, document)
+        }).map_err(|e| AppError::Database(format!("Failed to store chunks: {}", e)))
+    }
+    
+    fn delete_document_chunks(tx: &Transaction, collection_id: &str, document_id: &str) -> rusqlite::Result<()> {
+        tx.execute(
+            "DELETE FROM chunks_fts WHERE chunk_id IN
+                (SELECT id FROM chunks WHERE collection_id = ?1 AND document_id = ?2)",
+            params![collection_id, document_id],
+        )?;
+        tx.execute(
+            "DELETE FROM chunk_embeddings WHERE chunk_id IN
+                (SELECT id FROM chunks WHERE collection_id = ?1 AND document_id = ?2)",
+            params![collection_id, document_id],
+        )?;
+        tx.execute(
+            "DELETE FROM chunks WHERE collection_id = ?1 AND document_id = ?2",
+            params![collection_id, document_id],
+        )?;
+        Ok(())
+    }
+    
+    /// Remove a document's chunks and registry entry from a collection
+    pub fn remove_document(&self, collection_id: &str, document_id: &str) -> Result<()> {
+        self.write(|tx| {
+            Self::delete_document_chunks(tx, collection_id, document_id)?;
+            tx.execute(
+                "DELETE FROM documents WHERE collection_id = ?1 AND id = ?2",
+                params![collection_id, document_id],
+            )?;
+            Ok(())
+        }).map_err(|e| AppError::Database(format!("Failed to remove document: {}", e)))
+    }
+    
+    /// Search a collection for similar chunks using cosine similarity.
+    /// The metadata filter, if any, is applied in SQL before scoring.
+    ///
+    /// Stored vectors are unit length, so scoring is a dot product with the
+    /// normalized query. Full-precision chunks are scored exactly in one
+    /// pass. Quantized chunks are ranked by their int8 or binary estimate
+    /// first, and only the best candidates are rescored against their
+    /// full-precision vectors.
+    pub fn search(
+        &self,
+        collection_id: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        let mut query = query_embedding.to_vec();
+        quantization::normalize(&mut query);
+        let query_bits = quantization::sign_bits(&query);
+        
+        let (filter_sql, filter_params) = Self::filter_clause(filter, 2);
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.id, c.embedding_format, c.embedding FROM chunks c WHERE c.collection_id = ?1{}",
+            filter_sql
+        )).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+        
+        let mut query_params = vec![Value::Text(collection_id.to_string())];
+        query_params.extend(filter_params);
+        
+        let rows = stmt.query_map(params_from_iter(query_params), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Vec<u8>>(2)?))
         }).map_err(|e| AppError::Database(format!("Failed to query chunks: {}", e)))?;
         
-        // Calculate cosine similarity for each chunk
-        let mut results: Vec<SearchResult> = chunks
-            .filter_map(|c| c.ok())
+        // First pass: score every candidate from its stored representation
+        let mut has_quantized = false;
+        let mut candidates: Vec<(String, f32)> = rows
+            .filter_map(|r| r.ok())
+            .map(|(id, format, bytes)| {
+                let format = Quantization::parse(&format);
+                has_quantized |= format != Quantization::None;
+                (id, quantization::similarity(&query, &query_bits, &bytes, format))
+            })
+            .collect();
+        
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.truncate(if has_quantized {
+            top_k.saturating_mul(RESCORE_FACTOR).max(MIN_RESCORE_CANDIDATES)
+        } else {
+            top_k
+        });
+        
+        // Second pass: load the survivors with their full-precision vectors
+        // and score them exactly
+        let ids: Vec<String> = candidates.into_iter().map(|(id, _)| id).collect();
+        let mut results: Vec<SearchResult> = Self::load_chunks(&conn, &ids)?
+            .into_iter()
             .map(|chunk| {
-                let score = Self::cosine_similarity(query_embedding, &chunk.embedding);
-                SearchResult { chunk, score }
+                let score = quantization::dot(&query, &chunk.embedding);
+                SearchResult { chunk, score, keyword_score: None, fusion_score: None, rerank_score: None }
             })
             .collect();
         
@@ -135,38 +673,150 @@ impl VectorStore {
         Ok(results)
     }
     
-    /// Get all chunks for a document
-    #[allow(dead_code)]
-    pub fn get_document_chunks(&self, document_id: &str) -> Result<Vec<DocumentChunk>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, document_id, document_name, content, embedding, chunk_index, token_count 
-             FROM chunks WHERE document_id = ?1 ORDER BY chunk_index"
-        ).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+    /// Load chunks by ID
+    fn load_chunks(conn: &Connection, ids: &[String]) -> Result<Vec<DocumentChunk>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
         
-        let chunks = stmt.query_map(params![document_id], |row| {
-            let embedding_bytes: Vec<u8> = row.get(4)?;
-            let embedding = Self::bytes_to_embedding(&embedding_bytes);
-            
-            Ok(DocumentChunk {
-                id: row.get(0)?,
-                document_id: row.get(1)?,
-                document_name: row.get(2)?,
-                content: row.get(3)?,
-                embedding,
-                chunk_index: row.get(5)?,
-                token_count: row.get(6)?,
-            })
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM chunks c {} WHERE c.id IN ({})",
+            CHUNK_COLUMNS, CHUNK_EMBEDDING_JOIN, placeholders
+        )).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+        
+        let chunks = stmt.query_map(params_from_iter(ids), Self::chunk_from_row)
+            .map_err(|e| AppError::Database(format!("Failed to query chunks: {}", e)))?;
+        
+        Ok(chunks.filter_map(|c| c.ok()).collect())
+    }
+    
+    /// Search a collection for chunks matching the query terms, ranked by BM25
+    pub fn keyword_search(
+        &self,
+        collection_id: &str,
+        query: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        let match_expr = match Self::fts_match_expression(query) {
+            Some(expr) => expr,
+            None => return Ok(Vec::new()),
+        };
+        
+        let mut query_embedding = query_embedding.to_vec();
+        quantization::normalize(&mut query_embedding);
+        
+        let (filter_sql, filter_params) = Self::filter_clause(filter, 4);
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, bm25(chunks_fts) AS rank
+             FROM chunks_fts
+             JOIN chunks c ON c.id = chunks_fts.chunk_id
+             {}
+             WHERE chunks_fts MATCH ?1 AND c.collection_id = ?2{}
+             ORDER BY rank
+             LIMIT ?3",
+            CHUNK_COLUMNS, CHUNK_EMBEDDING_JOIN, filter_sql
+        )).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+        
+        let mut query_params = vec![
+            Value::Text(match_expr),
+            Value::Text(collection_id.to_string()),
+            Value::Integer(top_k as i64),
+        ];
+        query_params.extend(filter_params);
+        
+        let rows = stmt.query_map(params_from_iter(query_params), |row| {
+            let rank: f64 = row.get(15)?;
+            Ok((Self::chunk_from_row(row)?, rank))
         }).map_err(|e| AppError::Database(format!("Failed to query chunks: {}", e)))?;
         
+        // SQLite's bm25() returns lower-is-better values; flip the sign so
+        // that, like cosine similarity, a higher score means more relevant.
+        Ok(rows
+            .filter_map(|r| r.ok())
+            .map(|(chunk, rank)| {
+                let score = quantization::dot(&query_embedding, &chunk.embedding);
+                SearchResult { chunk, score, keyword_score: Some(-rank as f32), fusion_score: None, rerank_score: None }
+            })
+            .collect())
+    }
+    
+    /// Combine vector and BM25 keyword rankings with reciprocal-rank fusion.
+    /// `keyword_weight` in 0.0..=1.0 shifts the balance towards keyword hits.
+    pub fn hybrid_search(
+        &self,
+        collection_id: &str,
+        query: &str,
+        query_embedding: &[f32],
+        top_k: usize,
+        keyword_weight: f32,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        let keyword_weight = keyword_weight.clamp(0.0, 1.0);
+        // Fuse over a wider candidate pool than we return so that a chunk
+        // ranked moderately by both retrievers can still surface.
+        let candidates = top_k.saturating_mul(4).max(20);
+        
+        let vector_results = self.search(collection_id, query_embedding, candidates, filter)?;
+        let keyword_results = self.keyword_search(collection_id, query, query_embedding, candidates, filter)?;
+        
+        let mut fused: HashMap<String, SearchResult> = HashMap::new();
+        
+        for (rank, result) in vector_results.into_iter().enumerate() {
+            let contribution = (1.0 - keyword_weight) / (RRF_K + rank as f32 + 1.0);
+            fused.insert(result.chunk.id.clone(), SearchResult {
+                fusion_score: Some(contribution),
+                ..result
+            });
+        }
+        
+        for (rank, result) in keyword_results.into_iter().enumerate() {
+            let contribution = keyword_weight / (RRF_K + rank as f32 + 1.0);
+            fused.entry(result.chunk.id.clone())
+                .and_modify(|existing| {
+                    existing.keyword_score = result.keyword_score;
+                    existing.fusion_score = Some(existing.fusion_score.unwrap_or(0.0) + contribution);
+                })
+                .or_insert(SearchResult {
+                    fusion_score: Some(contribution),
+                    ..result
+                });
+        }
+        
+        let mut results: Vec<SearchResult> = fused.into_values().collect();
+        results.sort_by(|a, b| {
+            b.fusion_score.partial_cmp(&a.fusion_score).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(top_k);
+        
+        Ok(results)
+    }
+    
+    /// Get all chunks for a document
+    pub fn get_document_chunks(&self, collection_id: &str, document_id: &str) -> Result<Vec<DocumentChunk>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM chunks c {}
+             WHERE c.collection_id = ?1 AND c.document_id = ?2
+             ORDER BY c.chunk_index",
+            CHUNK_COLUMNS, CHUNK_EMBEDDING_JOIN
+        )).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+        
+        let chunks = stmt.query_map(params![collection_id, document_id], Self::chunk_from_row)
+            .map_err(|e| AppError::Database(format!("Failed to query chunks: {}", e)))?;
+        
         Ok(chunks.filter_map(|c| c.ok()).collect())
     }
     
-    /// Check if a document is already indexed
-    #[allow(dead_code)]
-    pub fn document_exists(&self, document_id: &str) -> Result<bool> {
-        let count: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM chunks WHERE document_id = ?1",
-            params![document_id],
+    /// Check if a document is registered in a collection
+    pub fn document_exists(&self, collection_id: &str, document_id: &str) -> Result<bool> {
+        let conn = self.lock()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM documents WHERE collection_id = ?1 AND id = ?2",
+            params![collection_id, document_id],
             |row| row.get(0),
         ).map_err(|e| AppError::Database(format!("Failed to check document: {}", e)))?;
         
@@ -174,48 +824,179 @@ impl VectorStore {
     }
     
     /// Get chunk count for stats
-    pub fn get_chunk_count(&self) -> Result<i64> {
-        let count: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM chunks",
-            [],
+    pub fn get_chunk_count(&self, collection_id: &str) -> Result<i64> {
+        let conn = self.lock()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM chunks WHERE collection_id = ?1",
+            params![collection_id],
             |row| row.get(0),
         ).map_err(|e| AppError::Database(format!("Failed to count chunks: {}", e)))?;
         
         Ok(count)
     }
     
-    // Utility: Convert embedding to bytes for storage
-    fn embedding_to_bytes(embedding: &[f32]) -> Vec<u8> {
-        embedding.iter()
-            .flat_map(|f| f.to_le_bytes())
-            .collect()
+    /// Get registered document count for stats
+    pub fn get_document_count(&self, collection_id: &str) -> Result<i64> {
+        let conn = self.lock()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM documents WHERE collection_id = ?1",
+            params![collection_id],
+            |row| row.get(0),
+        ).map_err(|e| AppError::Database(format!("Failed to count documents: {}", e)))?;
+        
+        Ok(count)
     }
     
-    // Utility: Convert bytes back to embedding
-    fn bytes_to_embedding(bytes: &[u8]) -> Vec<f32> {
-        bytes.chunks(4)
-            .map(|chunk| {
-                let arr: [u8; 4] = chunk.try_into().unwrap_or([0, 0, 0, 0]);
-                f32::from_le_bytes(arr)
-            })
-            .collect()
-    }
+    // ============= Maintenance =============
     
-    // Cosine similarity between two vectors
-    fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-        if a.len() != b.len() || a.is_empty() {
-            return 0.0;
+    /// Convert every stored embedding to the current format, then VACUUM so
+    /// the space freed goes back to the filesystem. Returns the number of
+    /// chunks converted.
+    ///
+    /// Quantized chunks keep their full-precision vectors, so converting in
+    /// either direction loses nothing.
+    pub fn reencode_embeddings(&self) -> Result<usize> {
+        let quantization = self.quantization();
+        let mut converted = 0;
+        let mut last_rowid = 0i64;
+        
+        // Work in batches so memory stays bounded on large indexes and
+        // searches can run between them
+        loop {
+            let batch: Vec<(i64, String, Option<Vec<u8>>)> = {
+                let conn = self.lock()?;
+                let mut stmt = conn.prepare(
+                    "SELECT c.rowid, c.id, CASE WHEN c.embedding_format = 'f32' THEN c.embedding ELSE e.embedding END
+                     FROM chunks c LEFT JOIN chunk_embeddings e ON e.chunk_id = c.id
+                     WHERE c.rowid > ?1 AND c.embedding_format != ?2
+                     ORDER BY c.rowid LIMIT ?3",
+                ).map_err(|e| AppError::Database(format!("Failed to prepare query: {}", e)))?;
+                let rows = stmt.query_map(params![last_rowid, quantization.as_format(), REENCODE_BATCH as i64], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                }).map_err(|e| AppError::Database(format!("Failed to query chunks: {}", e)))?;
+                rows.collect::<rusqlite::Result<_>>()
+                    .map_err(|e| AppError::Database(format!("Failed to read chunks: {}", e)))?
+            };
+            let Some((rowid, _, _)) = batch.last() else {
+                break;
+            };
+            last_rowid = *rowid;
+            
+            converted += self.write(|tx| {
+                let mut update = tx.prepare("UPDATE chunks SET embedding = ?1, embedding_format = ?2 WHERE rowid = ?3")?;
+                let mut delete_full = tx.prepare("DELETE FROM chunk_embeddings WHERE chunk_id = ?1")?;
+                let mut insert_full = tx.prepare("INSERT INTO chunk_embeddings (chunk_id, embedding) VALUES (?1, ?2)")?;
+                let mut converted = 0;
+                // A chunk missing its full-precision vector can't be converted
+                for (rowid, id, bytes) in batch.iter().filter_map(|(rowid, id, bytes)| Some((rowid, id, bytes.as_ref()?))) {
+                    let mut embedding = quantization::from_bytes(bytes);
+                    quantization::normalize(&mut embedding);
+                    update.execute(params![quantization::encode(&embedding, quantization), quantization.as_format(), rowid])?;
+                    delete_full.execute(params![id])?;
+                    if quantization != Quantization::None {
+                        insert_full.execute(params![id, quantization::to_bytes(&embedding)])?;
+                    }
+                    converted += 1;
+                }
+                Ok(converted)
+            })?;
         }
         
-        let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        self.lock()?.execute_batch("VACUUM")
+            .map_err(|e| AppError::Database(format!("Failed to compact database: {}", e)))?;
         
-        if norm_a == 0.0 || norm_b == 0.0 {
-            return 0.0;
+        Ok(converted)
+    }
+    
+    // Utility: Render an optional metadata filter as an `AND (...)` clause
+    fn filter_clause(filter: Option<&MetadataFilter>, first_param: usize) -> (String, Vec<Value>) {
+        match filter {
+            Some(filter) => {
+                let (sql, params) = filter.to_sql(first_param);
+                (format!(" AND {}", sql), params)
+            }
+            None => (String::new(), Vec::new()),
         }
+    }
+    
+    // Utility: Build a chunk from a row selected with CHUNK_COLUMNS
+    fn chunk_from_row(row: &Row) -> rusqlite::Result<DocumentChunk> {
+        let embedding_bytes: Option<Vec<u8>> = row.get(5)?;
+        let metadata: String = row.get(8)?;
+        let char_start: Option<usize> = row.get(9)?;
+        let location = match char_start {
+            Some(char_start) => Some(ChunkLocation {
+                char_start,
+                char_end: row.get(10)?,
+                line_start: row.get(11)?,
+                line_end: row.get(12)?,
+                page_start: row.get(13)?,
+                page_end: row.get(14)?,
+            }),
+            None => None,
+        };
+        
+        Ok(DocumentChunk {
+            id: row.get(0)?,
+            collection_id: row.get(1)?,
+            document_id: row.get(2)?,
+            document_name: row.get(3)?,
+            content: row.get(4)?,
+            embedding: embedding_bytes.map(|bytes| quantization::from_bytes(&bytes)).unwrap_or_default(),
+            chunk_index: row.get(6)?,
+            token_count: row.get(7)?,
+            metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+            location,
+        })
+    }
+    
+    // Utility: Build a registry entry from a `documents` row
+    fn document_from_row(row: &Row) -> rusqlite::Result<DocumentRecord> {
+        let status: String = row.get(10)?;
+        
+        Ok(DocumentRecord {
+            id: row.get(0)?,
+            collection_id: row.get(1)?,
+            name: row.get(2)?,
+            path: row.get(3)?,
+            size: row.get(4)?,
+            content_hash: row.get(5)?,
+            mtime: row.get(6)?,
+            chunk_count: row.get(7)?,
+            embedding_model: row.get(8)?,
+            indexed_at: row.get(9)?,
+            status: IndexStatus::parse(&status),
+            last_error: row.get(11)?,
+            chunking_strategy: row.get(12)?,
+        })
+    }
+    
+    // Utility: Build a collection from (id, name, created_at, chunk_count)
+    fn collection_from_row(row: &Row) -> rusqlite::Result<Collection> {
+        Ok(Collection {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            chunk_count: row.get(3)?,
+        })
+    }
+    
+    // Utility: Turn free text into an FTS5 query that ORs every term.
+    // Each term is quoted so that punctuation in identifiers or error codes
+    // (e.g. `E0502`, `foo_bar`, `v1.2`) can't be parsed as FTS5 syntax.
+    fn fts_match_expression(query: &str) -> Option<String> {
+        let terms: Vec<String> = query
+            .split(|c: char| c.is_whitespace() || c == '"')
+            .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()))
+            .filter(|t| !t.is_empty())
+            .map(|t| format!("\"{}\"", t))
+            .collect();
         
-        dot_product / (norm_a * norm_b)
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" OR "))
+        }
     }
 }
 
@@ -225,11 +1006,288 @@ mod tests {
     
     #[test]
     fn test_cosine_similarity() {
-        let a = vec![1.0, 0.0, 0.0];
-        let b = vec![1.0, 0.0, 0.0];
-        assert!((VectorStore::cosine_similarity(&a, &b) - 1.0).abs() < 0.001);
+        let store = test_store();
+        store_chunk(&store, test_chunk("a", "same direction", vec![2.0, 0.0, 0.0]));
+        store_chunk(&store, test_chunk("c", "orthogonal", vec![0.0, 3.0, 0.0]));
+        
+        let results = store.search(DEFAULT_COLLECTION, &[1.0, 0.0, 0.0], 2, None).unwrap();
+        assert!((results[0].score - 1.0).abs() < 0.001);
+        assert!((results[1].score - 0.0).abs() < 0.001);
+    }
+    
+    fn test_store() -> VectorStore {
+        VectorStore::open_in_memory().unwrap()
+    }
+    
+    fn test_chunk(id: &str, content: &str, embedding: Vec<f32>) -> DocumentChunk {
+        DocumentChunk {
+            id: id.to_string(),
+            collection_id: DEFAULT_COLLECTION.to_string(),
+            document_id: id.to_string(),
+            document_name: format!("{}.md", id),
+            content: content.to_string(),
+            embedding,
+            chunk_index: 0,
+            token_count: 1,
+            metadata: Metadata::new(),
+            location: None,
+        }
+    }
+    
+    fn test_record(id: &str, collection_id: &str) -> DocumentRecord {
+        DocumentRecord {
+            id: id.to_string(),
+            collection_id: collection_id.to_string(),
+            name: format!("{}.md", id),
+            path: format!("/notes/{}.md", id),
+            size: 42,
+            content_hash: "abc".to_string(),
+            mtime: None,
+            chunk_count: 0,
+            embedding_model: "text-embedding-004".to_string(),
+            indexed_at: None,
+            status: IndexStatus::Indexed,
+            last_error: None,
+            chunking_strategy: String::new(),
+        }
+    }
+    
+    /// Store a single chunk as its own document
+    fn store_chunk(store: &VectorStore, chunk: DocumentChunk) {
+        let record = DocumentRecord { chunk_count: 1, ..test_record(&chunk.document_id, &chunk.collection_id) };
+        store.store_chunks(&record, &[chunk]).unwrap();
+    }
+    
+    #[test]
+    fn test_hybrid_search_surfaces_exact_keyword_match() {
+        let store = test_store();
+        store_chunk(&store, test_chunk("a", "General notes about error handling", vec![1.0, 0.0]));
+        store_chunk(&store, test_chunk("b", "The build fails with E0502 on borrow", vec![0.0, 1.0]));
+        
+        let query_embedding = vec![1.0, 0.0];
+        let vector_only = store.search(DEFAULT_COLLECTION, &query_embedding, 1, None).unwrap();
+        assert_eq!(vector_only[0].chunk.id, "a");
+        
+        let hybrid = store.hybrid_search(DEFAULT_COLLECTION, "E0502", &query_embedding, 1, 0.7, None).unwrap();
+        assert_eq!(hybrid[0].chunk.id, "b");
+        assert!(hybrid[0].keyword_score.is_some());
+    }
+    
+    #[test]
+    fn test_fts_match_expression_quotes_terms() {
+        assert_eq!(
+            VectorStore::fts_match_expression("what is \"foo_bar\"?").as_deref(),
+            Some("\"what\" OR \"is\" OR \"foo_bar\"")
+        );
+        assert_eq!(VectorStore::fts_match_expression("  ?! "), None);
+    }
+    
+    #[test]
+    fn test_collections_scope_search() {
+        let store = test_store();
+        let client = store.create_collection("Client project").unwrap();
+        
+        store_chunk(&store, test_chunk("personal", "grocery list", vec![1.0, 0.0]));
+        store_chunk(&store, DocumentChunk {
+            collection_id: client.id.clone(),
+            ..test_chunk("client", "grocery delivery contract", vec![0.9, 0.1])
+        });
+        
+        let results = store.search(&client.id, &[1.0, 0.0], 10, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, "client");
+        
+        let keyword = store.keyword_search(&client.id, "grocery", &[1.0, 0.0], 10, None).unwrap();
+        assert_eq!(keyword.len(), 1);
+        
+        assert!(store.create_collection(" Client project ").is_err());
+        let renamed = store.rename_collection(&client.id, "Acme").unwrap();
+        assert_eq!(renamed.name, "Acme");
+        assert_eq!(renamed.chunk_count, 1);
+        
+        store.delete_collection(&client.id).unwrap();
+        assert_eq!(store.list_collections().unwrap().len(), 1);
+        assert_eq!(store.get_chunk_count(DEFAULT_COLLECTION).unwrap(), 1);
+        assert!(store.delete_collection(DEFAULT_COLLECTION).is_err());
+    }
+    
+    #[test]
+    fn test_metadata_filter_applies_before_scoring() {
+        let store = test_store();
+        let metadata = |value: serde_json::Value| value.as_object().cloned().unwrap();
+        
+        store_chunk(&store, DocumentChunk {
+            metadata: metadata(serde_json::json!({"tags": ["legal"], "modified": "2026-03-01T10:00:00Z"})),
+            ..test_chunk("contract", "Termination clause", vec![0.5, 0.5])
+        });
+        store_chunk(&store, DocumentChunk {
+            metadata: metadata(serde_json::json!({"tags": ["legal"], "modified": "2025-06-01T10:00:00Z"})),
+            ..test_chunk("old", "Termination clause, old draft", vec![1.0, 0.0])
+        });
+        store_chunk(&store, test_chunk("notes", "Termination notes", vec![1.0, 0.0]));
+        
+        let filter = MetadataFilter::parse(r#"tag = "legal" AND modified > 2026-01-01"#).unwrap();
+        let results = store.search(DEFAULT_COLLECTION, &[1.0, 0.0], 10, Some(&filter)).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, "contract");
+        assert_eq!(results[0].chunk.metadata["tags"], serde_json::json!(["legal"]));
+        
+        let keyword = store.keyword_search(DEFAULT_COLLECTION, "termination", &[1.0, 0.0], 10, Some(&filter)).unwrap();
+        assert_eq!(keyword.len(), 1);
+    }
+    
+    #[test]
+    fn test_filter_by_page_and_heading() {
+        let store = test_store();
+        let chunk = |id: &str, pages: Option<(usize, usize)>, headings: &[&str]| DocumentChunk {
+            metadata: serde_json::json!({"headings": headings}).as_object().cloned().unwrap(),
+            location: Some(ChunkLocation {
+                char_start: 0,
+                char_end: 1,
+                line_start: 1,
+                line_end: 1,
+                page_start: pages.map(|p| p.0),
+                page_end: pages.map(|p| p.1),
+            }),
+            ..test_chunk(id, id, vec![1.0, 0.0])
+        };
+        store_chunk(&store, chunk("intro", Some((1, 2)), &["Guide"]));
+        store_chunk(&store, chunk("install", Some((13, 15)), &["Guide", "Install"]));
+        store_chunk(&store, chunk("readme", None, &["Install"]));
+        
+        let matching = |filter: &str| -> Vec<String> {
+            let filter = MetadataFilter::parse(filter).unwrap();
+            let mut ids: Vec<String> = store.search(DEFAULT_COLLECTION, &[1.0, 0.0], 10, Some(&filter))
+                .unwrap()
+                .into_iter()
+                .map(|r| r.chunk.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        
+        assert_eq!(matching("page >= 14"), vec!["install"]);
+        assert_eq!(matching("page = 14"), vec!["install"]);
+        assert_eq!(matching("page != 14"), vec!["intro"]);
+        assert_eq!(matching("page < 13"), vec!["intro"]);
+        assert_eq!(matching(r#"heading = "Install""#), vec!["install", "readme"]);
+        assert_eq!(matching("heading = Guide AND page > 2"), vec!["install"]);
+    }
+    
+    #[test]
+    fn test_document_registry() {
+        let store = test_store();
+        let record = DocumentRecord { status: IndexStatus::Indexing, ..test_record("doc", DEFAULT_COLLECTION) };
+        let location = ChunkLocation { char_start: 0, char_end: 5, line_start: 1, line_end: 1, page_start: Some(14), page_end: Some(14) };
+        store.upsert_document(&record).unwrap();
+        store.store_chunks(
+            &DocumentRecord { chunk_count: 1, status: IndexStatus::Indexed, ..record },
+            &[DocumentChunk { document_id: "doc".to_string(), location: Some(location), ..test_chunk("a", "first", vec![1.0, 0.0]) }],
+        ).unwrap();
+        
+        let documents = store.list_documents(DEFAULT_COLLECTION).unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].status, IndexStatus::Indexed);
+        assert_eq!(documents[0].chunk_count, 1);
+        assert_eq!(store.get_document(DEFAULT_COLLECTION, "doc").unwrap().unwrap().status, IndexStatus::Indexed);
+        assert!(store.get_document("other", "doc").unwrap().is_none());
+        let chunks = store.get_document_chunks(DEFAULT_COLLECTION, "doc").unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].location, Some(location));
+        
+        store.remove_document(DEFAULT_COLLECTION, "doc").unwrap();
+        assert!(!store.document_exists(DEFAULT_COLLECTION, "doc").unwrap());
+        assert_eq!(store.get_chunk_count(DEFAULT_COLLECTION).unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_store_chunks_replaces_whole_document() {
+        let store = test_store();
+        let chunk = |id: &str, content: &str| DocumentChunk {
+            document_id: "doc".to_string(),
+            ..test_chunk(id, content, vec![1.0, 0.0])
+        };
+        let record = test_record("doc", DEFAULT_COLLECTION);
+        
+        store.store_chunks(
+            &DocumentRecord { chunk_count: 2, ..record.clone() },
+            &[chunk("a", "alpha"), chunk("b", "beta")],
+        ).unwrap();
+        store.store_chunks(&DocumentRecord { chunk_count: 1, ..record.clone() }, &[chunk("c", "gamma")]).unwrap();
+        assert_eq!(store.get_chunk_count(DEFAULT_COLLECTION).unwrap(), 1);
+        assert!(store.keyword_search(DEFAULT_COLLECTION, "alpha", &[1.0, 0.0], 10, None).unwrap().is_empty());
+        
+        // Chunks from another document are rejected without touching the stored set
+        assert!(store.store_chunks(&record, &[test_chunk("other", "delta", vec![1.0, 0.0])]).is_err());
+        
+        // A failed re-index keeps the previous chunks and their registry details
+        store.set_document_status(&DocumentRecord {
+            status: IndexStatus::Failed,
+            last_error: Some("embedding failed".to_string()),
+            ..record
+        }).unwrap();
+        let documents = store.list_documents(DEFAULT_COLLECTION).unwrap();
+        assert_eq!(documents[0].status, IndexStatus::Failed);
+        assert_eq!(documents[0].chunk_count, 1);
+        assert_eq!(store.get_document_chunks(DEFAULT_COLLECTION, "doc").unwrap()[0].id, "c");
+    }
+    
+    #[test]
+    fn test_quantized_search_rescores_candidates() {
+        for quantization in [Quantization::Int8, Quantization::Binary] {
+            let store = test_store().with_quantization(quantization);
+            store_chunk(&store, test_chunk("near", "near", vec![0.9, 0.1, 0.3, -0.2]));
+            store_chunk(&store, test_chunk("far", "far", vec![-0.5, 0.8, -0.1, 0.4]));
+            store_chunk(&store, test_chunk("mid", "mid", vec![0.4, 0.6, 0.2, 0.1]));
+            
+            let results = store.search(DEFAULT_COLLECTION, &[1.0, 0.0, 0.3, -0.2], 2, None).unwrap();
+            let ids: Vec<&str> = results.iter().map(|r| r.chunk.id.as_str()).collect();
+            assert_eq!(ids, vec!["near", "mid"]);
+            
+            // Rescored against the full-precision vector, not the quantized one
+            let mut query = vec![1.0, 0.0, 0.3, -0.2];
+            let mut near = vec![0.9, 0.1, 0.3, -0.2];
+            quantization::normalize(&mut query);
+            quantization::normalize(&mut near);
+            assert!((results[0].score - quantization::dot(&query, &near)).abs() < 1e-6);
+            assert_eq!(results[0].chunk.embedding, near);
+            
+            let scanned_bytes: i64 = store.lock().unwrap()
+                .query_row("SELECT LENGTH(embedding) FROM chunks WHERE id = 'near'", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(scanned_bytes, if quantization == Quantization::Binary { 1 } else { 4 + 4 });
+        }
+    }
+    
+    #[test]
+    fn test_reencode_embeddings_converts_existing_chunks() {
+        let store = test_store();
+        store_chunk(&store, test_chunk("a", "a", vec![0.9, 0.1, 0.3, -0.2]));
+        store_chunk(&store, test_chunk("b", "b", vec![-0.5, 0.8, -0.1, 0.4]));
+        let embedding_bytes = || -> i64 {
+            store.lock().unwrap()
+                .query_row("SELECT SUM(LENGTH(embedding)) FROM chunks WHERE embedding_format = 'int8'", [], |row| row.get::<_, Option<i64>>(0))
+                .unwrap()
+                .unwrap_or(0)
+        };
+        
+        store.set_quantization(Quantization::Int8);
+        assert_eq!(store.reencode_embeddings().unwrap(), 2);
+        assert_eq!(embedding_bytes(), 2 * (4 + 4));
+        assert_eq!(store.reencode_embeddings().unwrap(), 0);
+        
+        let results = store.search(DEFAULT_COLLECTION, &[0.9, 0.1, 0.3, -0.2], 1, None).unwrap();
+        assert_eq!(results[0].chunk.id, "a");
+        assert!(results[0].score > 0.99);
         
-        let c = vec![0.0, 1.0, 0.0];
-        assert!((VectorStore::cosine_similarity(&a, &c) - 0.0).abs() < 0.001);
+        // Converting back restores the exact full-precision vectors
+        let original = store.get_document_chunks(DEFAULT_COLLECTION, "b").unwrap()[0].embedding.clone();
+        store.set_quantization(Quantization::None);
+        assert_eq!(store.reencode_embeddings().unwrap(), 2);
+        assert_eq!(store.get_document_chunks(DEFAULT_COLLECTION, "b").unwrap()[0].embedding, original);
+        let side_rows: i64 = store.lock().unwrap()
+            .query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(side_rows, 0);
     }
 }
@@ -12,14 +12,52 @@ interface SearchResult {
     document_name: string;
     content: string;
     score: number;
+    keyword_score?: number | null;
+    rerank_score?: number | null;
     chunk_index: number;
+    metadata?: { headings?: string[] };
+    location?: ChunkLocation | null;
+    filter_reason?: "below_threshold" | "chunk_limit" | "duplicate";
 }
 
+interface ChunkLocation {
+    char_start: number;
+    char_end: number;
+    line_start: number;
+    line_end: number;
+    page_start?: number | null;
+    page_end?: number | null;
+}
+
+interface SearchResponse {
+    results: SearchResult[];
+    dropped: SearchResult[];
+    similarity_threshold: number;
+    max_chunks: number;
+}
+
+function formatLocation(location?: ChunkLocation | null): string {
+    if (!location) return "";
+    const { page_start, page_end, line_start, line_end } = location;
+    if (page_start != null) {
+        return page_end != null && page_end !== page_start ? `pp. ${page_start}–${page_end}` : `p. ${page_start}`;
+    }
+    return line_end !== line_start ? `lines ${line_start}–${line_end}` : `line ${line_start}`;
+}
+
+const FILTER_REASON_LABELS: Record<string, string> = {
+    below_threshold: "Below threshold",
+    chunk_limit: "Over chunk limit",
+    duplicate: "Near-duplicate",
+};
+
 export function RagDebugPanel() {
     const [stats, setStats] = useState<IndexStats | null>(null);
     const [loading, setLoading] = useState(false);
+    const [reencoding, setReencoding] = useState(false);
     const [testQuery, setTestQuery] = useState("");
     const [searchResults, setSearchResults] = useState<SearchResult[]>([]);
+    const [droppedResults, setDroppedResults] = useState<SearchResult[]>([]);
     const [searching, setSearching] = useState(false);
     const [error, setError] = useState<string | null>(null);
 
@@ -47,16 +85,17 @@ export function RagDebugPanel() {
         setSearching(true);
         setError(null);
         try {
-            const results = await invoke<SearchResult[]>("semantic_search", {
+            const response = await invoke<SearchResponse>("semantic_search", {
                 query: testQuery,
                 provider: "gemini",
                 apiKey: geminiProvider.apiKey,
-                topK: 5,
             });
-            setSearchResults(results);
+            setSearchResults(response.results);
+            setDroppedResults(response.dropped);
         } catch (e: any) {
             setError(e?.message || "Search failed");
             setSearchResults([]);
+            setDroppedResults([]);
         } finally {
             setSearching(false);
         }
@@ -68,11 +107,25 @@ export function RagDebugPanel() {
             await invoke("clear_index");
             setStats({ chunk_count: 0, indexed: false });
             setSearchResults([]);
+            setDroppedResults([]);
         } catch (e: any) {
             setError(e?.message || "Failed to clear");
         }
     };
 
+    const handleReencodeEmbeddings = async () => {
+        if (!confirm("Re-encode all embeddings in the configured format? This can take a while on large indexes.")) return;
+        setReencoding(true);
+        try {
+            await invoke<number>("reencode_embeddings");
+            await loadStats();
+        } catch (e: any) {
+            setError(e?.message || "Failed to re-encode embeddings");
+        } finally {
+            setReencoding(false);
+        }
+    };
+
     useEffect(() => {
         loadStats();
     }, []);
@@ -90,6 +143,14 @@ export function RagDebugPanel() {
                     >
                         <RefreshIcon size={14} className={loading ? "animate-spin" : ""} />
                     </button>
+                    <button
+                        onClick={handleReencodeEmbeddings}
+                        disabled={reencoding}
+                        className="px-1.5 py-1 hover:bg-bg-tertiary rounded transition-colors text-xs text-text-tertiary hover:text-text-primary"
+                        title="Re-encode embeddings in the configured format"
+                    >
+                        {reencoding ? <SpinnerIcon size={14} className="animate-spin" /> : "Re-encode"}
+                    </button>
                     <button
                         onClick={handleClearIndex}
                         className="p-1.5 hover:bg-error/20 rounded transition-colors text-text-tertiary hover:text-error"
@@ -163,9 +224,14 @@ export function RagDebugPanel() {
                         {searchResults.map((result, i) => (
                             <div key={i} className="p-2 bg-bg-tertiary rounded border border-border">
                                 <div className="flex items-center justify-between mb-1">
-                                    <span className="text-xs font-medium text-accent-primary">{result.document_name}</span>
+                                    <span className="text-xs font-medium text-accent-primary">
+                                        {result.document_name}
+                                        {result.metadata?.headings?.length ? ` › ${result.metadata.headings.join(" › ")}` : ""}
+                                        {result.location && ` · ${formatLocation(result.location)}`}
+                                    </span>
                                     <span className="text-xs text-text-tertiary">
                                         Score: {(result.score * 100).toFixed(1)}%
+                                        {result.rerank_score != null && ` · Rerank: ${(result.rerank_score * 100).toFixed(1)}%`}
                                     </span>
                                 </div>
                                 <div className="text-xs text-text-secondary line-clamp-2">{result.content}</div>
@@ -175,6 +241,26 @@ export function RagDebugPanel() {
                 </div>
             )}
 
+            {/* Dropped */}
+            {droppedResults.length > 0 && (
+                <div className="space-y-2">
+                    <div className="text-xs text-text-tertiary">Dropped ({droppedResults.length})</div>
+                    <div className="max-h-32 overflow-y-auto space-y-2">
+                        {droppedResults.map((result, i) => (
+                            <div key={i} className="p-2 bg-bg-tertiary rounded border border-border opacity-60">
+                                <div className="flex items-center justify-between mb-1">
+                                    <span className="text-xs font-medium text-text-secondary">{result.document_name}</span>
+                                    <span className="text-xs text-text-tertiary">
+                                        {FILTER_REASON_LABELS[result.filter_reason ?? ""] ?? "Dropped"} · {(result.score * 100).toFixed(1)}%
+                                    </span>
+                                </div>
+                                <div className="text-xs text-text-secondary line-clamp-1">{result.content}</div>
+                            </div>
+                        ))}
+                    </div>
+                </div>
+            )}
+
             {/* Info */}
             <div className="text-xs text-text-tertiary">
                 Requires Gemini API key. 512 token chunks.
@@ -189,7 +189,7 @@ export function Dashboard() {
         directory: false,
         filters: [{
           name: "Documents",
-          extensions: ["pdf", "txt", "md", "docx", "html", "py", "js", "ts", "rs", "java", "cpp", "c", "json", "yaml", "yml", "toml"]
+          extensions: ["pdf", "txt", "md", "docx", "pptx", "xlsx", "odt", "epub", "html", "py", "js", "ts", "rs", "java", "cpp", "c", "json", "yaml", "yml", "toml"]
         }]
       });
 
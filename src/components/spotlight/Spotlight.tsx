@@ -101,7 +101,7 @@ export function Spotlight() {
         multiple: true,
         filters: [{
           name: "Documents",
-          extensions: ["pdf", "txt", "md", "docx", "html", "py", "js", "ts", "rs", "java", "cpp", "c", "json", "yaml", "yml", "toml"]
+          extensions: ["pdf", "txt", "md", "docx", "pptx", "xlsx", "odt", "epub", "html", "py", "js", "ts", "rs", "java", "cpp", "c", "json", "yaml", "yml", "toml"]
         }]
       });
       if (selected) {
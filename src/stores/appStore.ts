@@ -674,7 +674,8 @@ export async function indexDocumentAsync(docId: string, docName: string, filePat
 }
 
 // Get relevant context for a query using semantic search
-export async function getSemanticContext(query: string): Promise<string> {
+// (turnId lets retries of the same turn reuse the expanded queries)
+export async function getSemanticContext(query: string, turnId?: string): Promise<string> {
   const provider = providers.value.find(p => p.id === "gemini" && p.apiKey);
   if (!provider) return "";
 
@@ -684,6 +685,7 @@ export async function getSemanticContext(query: string): Promise<string> {
       provider: "gemini",
       apiKey: provider.apiKey,
       maxTokens: 4000,
+      turnId,
     });
     return context;
   } catch (e) {
@@ -695,6 +697,9 @@ export async function getSemanticContext(query: string): Promise<string> {
 export function removeDocument(docId: string) {
   documents.value = documents.value.filter(d => d.id !== docId);
   saveDocuments();
+
+  // Drop it from the semantic index too; it may never have been indexed
+  invoke("remove_indexed_document", { documentId: docId }).catch(() => {});
 }
 
 // Folder Actions